@@ -0,0 +1,90 @@
+//! Mask operations and move generation on the positions of `bench::positions`.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use flood_it_bot::bench::positions;
+use flood_it_bot::game::State;
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+
+fn mask(c: &mut Criterion) {
+    let positions = positions();
+    let accessible: Vec<_> = positions
+        .iter()
+        .map(|state| !(state.player1 | state.player2 | state.walls))
+        .collect();
+
+    let mut group = c.benchmark_group("mask");
+    group.bench_function("bfs", |b| {
+        b.iter(|| {
+            for (state, accessible) in positions.iter().zip(&accessible) {
+                black_box(black_box(state.player1).bfs(*accessible));
+            }
+        });
+    });
+    group.bench_function("closer", |b| {
+        b.iter(|| {
+            for state in &positions {
+                black_box(black_box(state.player1).closer(state.player2, state.walls));
+            }
+        });
+    });
+    group.bench_function("closer_by_color", |b| {
+        b.iter(|| {
+            for state in &positions {
+                black_box(black_box(state.player1).closer_by_color(
+                    state.player2,
+                    state.walls,
+                    &state.colors,
+                ));
+            }
+        });
+    });
+    group.bench_function("sample", |b| {
+        let mut rng = Pcg64::seed_from_u64(0);
+        b.iter(|| {
+            for accessible in &accessible {
+                black_box(black_box(*accessible).sample(&mut rng));
+            }
+        });
+    });
+    group.finish();
+}
+
+fn game(c: &mut Criterion) {
+    let positions = positions();
+
+    let mut group = c.benchmark_group("game");
+    group.bench_function("generate", |b| {
+        let mut seed = 0;
+        b.iter(|| {
+            seed += 1;
+            black_box(State::generate(black_box(seed)))
+        });
+    });
+    group.bench_function("play_restore", |b| {
+        let mut positions = positions.clone();
+        b.iter(|| {
+            for state in &mut positions {
+                let checkpoint = state.checkpoint();
+                for color in state.valid_moves() {
+                    state.play(color);
+                    black_box(&state);
+                    state.restore(checkpoint);
+                }
+            }
+        });
+    });
+    group.bench_function("game_over", |b| {
+        b.iter(|| {
+            for state in &positions {
+                black_box(black_box(state).game_over());
+            }
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, mask, game);
+criterion_main!(benches);
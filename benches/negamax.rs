@@ -0,0 +1,50 @@
+//! Search speed with each eval, and with each game representation.
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use flood_it_bot::bench::{positions, search};
+use flood_it_bot::bot::{
+    Accessible, Captured, Closer, CloserColor, Eval, Quiescence, Race, Sealed, Weighted,
+};
+use flood_it_bot::game::State;
+use flood_it_bot::region::RegionState;
+
+const DEPTH: u32 = 5;
+
+fn eval<E: Eval>(c: &mut Criterion, name: &str, eval: &E, positions: &[State]) {
+    c.bench_function(&format!("eval/{name}"), |b| {
+        b.iter(|| {
+            for state in positions {
+                search(&mut state.clone(), eval, DEPTH);
+            }
+        });
+    });
+}
+
+fn evals(c: &mut Criterion) {
+    let positions = positions();
+    eval(c, "captured", &Captured, &positions);
+    eval(c, "accessible", &Accessible, &positions);
+    eval(c, "closer", &Closer, &positions);
+    eval(c, "closer_color", &CloserColor, &positions);
+    eval(c, "race", &Race(4), &positions);
+    eval(c, "sealed", &Sealed, &positions);
+    eval(c, "quiescence", &Quiescence::new(CloserColor), &positions);
+    eval(c, "weighted", &Weighted::default(), &positions);
+}
+
+fn representations(c: &mut Criterion) {
+    let mut group = c.benchmark_group("representation");
+    for (seed, state) in positions().into_iter().enumerate().take(2) {
+        group.bench_with_input(BenchmarkId::new("mask", seed), &state, |b, state| {
+            b.iter(|| search(&mut state.clone(), &Captured, DEPTH));
+        });
+        let regions = RegionState::new(state);
+        group.bench_with_input(BenchmarkId::new("regions", seed), &regions, |b, regions| {
+            b.iter(|| search(&mut regions.clone(), &Captured, DEPTH));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, evals, representations);
+criterion_main!(benches);
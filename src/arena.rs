@@ -0,0 +1,397 @@
+//! Playing games between players, and keeping score.
+
+use core::cmp::Ordering;
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::book::{Book, Booked};
+use crate::bot::{
+    Accessible, Captured, Closer, CloserColor, Deepening, Greedy, Negamax, Player, Pvs, Quiescence,
+    Race, Sealed, Weighted,
+};
+use crate::clock::{Clock, TimeControl};
+use crate::engine::ExternalPlayer;
+use crate::game::State;
+
+/// A built in player, by name.
+pub struct Contestant {
+    pub name: &'static str,
+    pub make: fn() -> Box<dyn Player>,
+}
+
+macro_rules! contestant {
+    ($name:expr, $make:expr) => {
+        Contestant {
+            name: $name,
+            make: || Box::new($make),
+        }
+    };
+
+    ($make:expr) => {
+        contestant!(stringify!($make), $make)
+    };
+}
+
+/// Running totals for one contestant over many matches.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContestantStats {
+    pub elo: f64,
+    pub total_elo: f64,
+    pub margin: i32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    pub forfeits: u32,
+    pub time: Duration,
+    pub rounds: u32,
+}
+
+impl Default for ContestantStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContestantStats {
+    pub fn new() -> Self {
+        Self {
+            elo: 400.0,
+            total_elo: 0.0,
+            margin: 0,
+            wins: 0,
+            losses: 0,
+            draws: 0,
+            forfeits: 0,
+            time: Duration::ZERO,
+            rounds: 0,
+        }
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    pub fn avg_time(&self) -> Duration {
+        self.time / self.games().max(1)
+    }
+
+    pub fn avg_elo(&self) -> f64 {
+        self.total_elo / f64::from(self.games().max(1))
+    }
+
+    pub fn avg_margin(&self) -> f64 {
+        f64::from(self.margin) / f64::from(self.games().max(1))
+    }
+}
+
+/// The combined result of both games played by two contestants on one seed.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatchResult {
+    pub p1: usize,
+    pub p2: usize,
+    pub margin: i32,
+    pub p1_time: Duration,
+    pub p2_time: Duration,
+    pub p1_forfeits: u32,
+    pub p2_forfeits: u32,
+    pub rounds: u32,
+}
+
+impl MatchResult {
+    /// Combines a game where `p1` moves first with the game on the same seed where `p2` does.
+    pub fn new(p1: usize, p2: usize, first: GameResult, second: GameResult) -> Self {
+        Self {
+            p1,
+            p2,
+            margin: first.margin - second.margin,
+            p1_time: first.p1_time + second.p2_time,
+            p2_time: first.p2_time + second.p1_time,
+            p1_forfeits: u32::from(first.p1_forfeited) + u32::from(second.p2_forfeited),
+            p2_forfeits: u32::from(first.p2_forfeited) + u32::from(second.p1_forfeited),
+            rounds: first.rounds + second.rounds,
+        }
+    }
+
+    pub fn update(&self, stats: &mut [ContestantStats]) {
+        const K: f64 = 16.0;
+
+        let ra = stats[self.p1].elo;
+        let rb = stats[self.p2].elo;
+
+        let ea = 1.0 / (1.0 + 10f64.powf((rb - ra) / 400.0));
+        let eb = 1.0 - ea;
+
+        let sa = match self.margin.cmp(&0) {
+            Ordering::Greater => 1.0,
+            Ordering::Less => 0.0,
+            Ordering::Equal => 0.5,
+        };
+        let sb = 1.0 - sa;
+
+        stats[self.p1].elo = ra + K * (sa - ea);
+        stats[self.p2].elo = rb + K * (sb - eb);
+
+        stats[self.p1].total_elo += stats[self.p1].elo;
+        stats[self.p2].total_elo += stats[self.p2].elo;
+
+        stats[self.p1].margin += self.margin;
+        stats[self.p2].margin -= self.margin;
+
+        stats[self.p1].time += self.p1_time;
+        stats[self.p2].time += self.p2_time;
+
+        stats[self.p1].forfeits += self.p1_forfeits;
+        stats[self.p2].forfeits += self.p2_forfeits;
+
+        stats[self.p1].rounds += self.rounds;
+        stats[self.p2].rounds += self.rounds;
+
+        match self.margin.cmp(&0) {
+            Ordering::Greater => {
+                stats[self.p1].wins += 1;
+                stats[self.p2].losses += 1;
+            }
+            Ordering::Less => {
+                stats[self.p1].losses += 1;
+                stats[self.p2].wins += 1;
+            }
+            Ordering::Equal => {
+                stats[self.p1].draws += 1;
+                stats[self.p2].draws += 1;
+            }
+        }
+    }
+}
+
+/// The result of a single game. `margin` is from player 1's perspective.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GameResult {
+    pub margin: i32,
+    pub p1_time: Duration,
+    pub p2_time: Duration,
+    /// Whether player 1 ran out of time or made an illegal move.
+    pub p1_forfeited: bool,
+    pub p2_forfeited: bool,
+    pub rounds: u32,
+}
+
+/// The players of the tournament, which can also be picked by name in the other tools.
+pub const CONTESTANTS: &[Contestant] = &[
+    contestant!(Greedy),
+    contestant!(Negamax(Captured, 6)),
+    contestant!(Negamax(Accessible, 6)),
+    contestant!(Negamax(Closer, 6)),
+    contestant!(Negamax(CloserColor, 6)),
+    contestant!(Negamax((Closer, Captured), 6)),
+    contestant!(Negamax((Accessible, Captured), 6)),
+    contestant!(Negamax((Accessible, Closer), 6)),
+    contestant!(Negamax((Closer, Accessible), 6)),
+    contestant!(Negamax((Closer, Accessible, Captured), 6)),
+    contestant!(Negamax((CloserColor, Accessible), 6)),
+    contestant!(Negamax((CloserColor, Accessible, Captured), 6)),
+    contestant!(Deepening((CloserColor, Accessible), 6)),
+    contestant!(Pvs((CloserColor, Accessible), 6)),
+    contestant!(Negamax(Race(4), 4)),
+    contestant!(Negamax(Sealed, 6)),
+    contestant!(
+        "Negamax(Quiescence(CloserColor), 6)",
+        Negamax(Quiescence::new(CloserColor), 6)
+    ),
+    contestant!(
+        "Negamax(Weighted, 6)",
+        Negamax(
+            Weighted::load_or_default(Weighted::PATH)
+                .unwrap_or_else(|error| panic!("cannot load `{}`: {error}", Weighted::PATH)),
+            6
+        )
+    ),
+];
+
+pub fn find_contestant(name: &str) -> Option<&'static Contestant> {
+    CONTESTANTS
+        .iter()
+        .find(|contestant| contestant.name == name)
+}
+
+/// Builds a contestant by name, an external engine from `engine:<command line>`, or a player
+/// that plays from the book at `Book::PATH` before handing over to another from `book:<spec>`.
+pub fn make_player(spec: &str) -> io::Result<Box<dyn Player>> {
+    if let Some(inner) = spec.strip_prefix("book:") {
+        Ok(Box::new(Booked {
+            book: Arc::new(Book::load(Book::PATH)?),
+            inner: make_player(inner)?,
+            min_games: Book::MIN_GAMES,
+        }))
+    } else if let Some(command) = spec.strip_prefix("engine:") {
+        let mut words = command.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty engine command"))?;
+        let args: Vec<_> = words.collect();
+        Ok(Box::new(ExternalPlayer::spawn(program, &args)?))
+    } else {
+        let contestant = find_contestant(spec).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("unknown contestant `{spec}`"),
+            )
+        })?;
+        Ok((contestant.make)())
+    }
+}
+
+/// Plays a game without time control.
+pub fn play_game(seed: u64, player1: &mut dyn Player, player2: &mut dyn Player) -> GameResult {
+    play_timed_game(seed, player1, player2, None)
+}
+
+/// Plays a game, under `time_control` if there is one. A player who runs out of time, fails or
+/// makes an illegal move forfeits, losing as if the opponent had captured every cell.
+pub fn play_timed_game(
+    seed: u64,
+    player1: &mut dyn Player,
+    player2: &mut dyn Player,
+    time_control: Option<TimeControl>,
+) -> GameResult {
+    let mut state = State::generate(seed);
+    let mut p1_time = Duration::ZERO;
+    let mut p2_time = Duration::ZERO;
+    let mut p1_clock = time_control.map(TimeControl::initial);
+    let mut p2_clock = p1_clock;
+
+    while !state.game_over() {
+        let (player, time, clock, opponent_clock): (&mut dyn Player, _, _, _) =
+            if state.player1_next() {
+                (player1, &mut p1_time, &mut p1_clock, p2_clock)
+            } else {
+                (player2, &mut p2_time, &mut p2_clock, p1_clock)
+            };
+
+        let move_clock = match (time_control, *clock, opponent_clock) {
+            (Some(time_control), Some(remaining), Some(opponent_remaining)) => Some(Clock {
+                remaining,
+                increment: time_control.increment(),
+                opponent_remaining,
+                opponent_increment: time_control.increment(),
+                per_move: matches!(time_control, TimeControl::MoveTime(_)),
+            }),
+            _ => None,
+        };
+        let start = Instant::now();
+        let color = player.try_play(&state, move_clock.as_ref());
+        let elapsed = start.elapsed();
+        *time += elapsed;
+
+        let flagged = match (time_control, *clock) {
+            (Some(time_control), Some(remaining)) => {
+                *clock = time_control.after_move(remaining, elapsed);
+                clock.is_none()
+            }
+            _ => false,
+        };
+        if flagged || !color.is_ok_and(|color| state.try_play(color).is_ok()) {
+            let cells = 256 - state.walls.count_ones() as i32;
+            let player1_forfeited = state.player1_next();
+            return GameResult {
+                margin: if player1_forfeited { -cells } else { cells },
+                p1_time,
+                p2_time,
+                p1_forfeited: player1_forfeited,
+                p2_forfeited: !player1_forfeited,
+                rounds: state.round,
+            };
+        }
+    }
+
+    GameResult {
+        margin: state.final_margin(),
+        p1_time,
+        p2_time,
+        p1_forfeited: false,
+        p2_forfeited: false,
+        rounds: state.round,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+    use crate::game::Color;
+
+    /// A player whose every move fails, like an engine that crashed.
+    struct Broken;
+
+    impl Player for Broken {
+        fn play(&mut self, state: &State) -> Color {
+            state.valid_moves().next().unwrap()
+        }
+
+        fn try_play(&mut self, _: &State, _: Option<&Clock>) -> io::Result<Color> {
+            Err(io::ErrorKind::UnexpectedEof.into())
+        }
+    }
+
+    /// A player that takes the given time for every move, whatever the clock says.
+    struct Slow(Duration);
+
+    impl Player for Slow {
+        fn play(&mut self, state: &State) -> Color {
+            std::thread::sleep(self.0);
+            Greedy.play(state)
+        }
+    }
+
+    #[test]
+    fn slow_player_flags() {
+        let time_control = Some(TimeControl::MoveTime(Duration::from_millis(20)));
+        let result = play_timed_game(
+            1,
+            &mut Greedy,
+            &mut Slow(Duration::from_millis(40)),
+            time_control,
+        );
+        assert!(result.p2_forfeited && !result.p1_forfeited);
+        assert_eq!(result.rounds, 1);
+        assert!(result.margin > 0);
+        assert!(result.p2_time >= Duration::from_millis(40));
+
+        let time_control = Some(TimeControl::Increment {
+            base: Duration::from_millis(100),
+            increment: Duration::ZERO,
+        });
+        let result = play_timed_game(
+            1,
+            &mut Slow(Duration::from_millis(35)),
+            &mut Greedy,
+            time_control,
+        );
+        assert!(result.p1_forfeited && !result.p2_forfeited);
+        // 100ms last for two moves of 35ms, but not for a third.
+        assert_eq!(result.rounds, 4);
+        assert!(result.margin < 0);
+
+        let time_control = Some(TimeControl::MoveTime(Duration::from_secs(10)));
+        let result = play_timed_game(1, &mut Slow(Duration::ZERO), &mut Greedy, time_control);
+        assert!(!result.p1_forfeited && !result.p2_forfeited);
+        assert_eq!(result.margin, play_game(1, &mut Greedy, &mut Greedy).margin);
+    }
+
+    #[test]
+    fn failing_player_forfeits() {
+        let result = play_game(1, &mut Greedy, &mut Broken);
+        assert!(result.p2_forfeited && !result.p1_forfeited);
+        assert_eq!(result.rounds, 1);
+        assert!(result.margin > 0);
+
+        let result = play_game(1, &mut Broken, &mut Greedy);
+        assert!(result.p1_forfeited && !result.p2_forfeited);
+        assert_eq!(result.rounds, 0);
+        assert!(result.margin < 0);
+    }
+}
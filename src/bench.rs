@@ -0,0 +1,115 @@
+//! Fixed workloads for measuring search speed, shared by the `bench` command and the criterion
+//! benchmarks.
+
+use std::time::{Duration, Instant};
+
+use crate::bot::{Eval, Greedy, Player, TABLE_BITS, deepen_with_table, negamax, pvs};
+use crate::game::{Counted, Position, State};
+
+/// The seeds of the boards in `positions`.
+pub const SEEDS: u64 = 8;
+/// The number of moves played on each board before searching it.
+pub const OPENING: u32 = 6;
+
+/// Positions a few moves into games played by `Greedy`, so that the same positions are searched
+/// on every run.
+pub fn positions() -> Vec<State> {
+    (0..SEEDS)
+        .map(|seed| {
+            let mut state = State::generate(seed);
+            while state.round < OPENING && !state.game_over() {
+                let color = Greedy.play(&state);
+                state.play(color);
+            }
+            state
+        })
+        .collect()
+}
+
+/// A full window search of `position` for the player to move.
+pub fn search<P: Position, E: Eval>(position: &mut P, eval: &E, depth: u32) -> i32 {
+    let sign = if position.state().player1_next() {
+        1
+    } else {
+        -1
+    };
+    negamax(position, eval, depth, -i32::MAX, i32::MAX, sign)
+}
+
+/// The nodes visited and the time taken by searches of every position.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Measurement {
+    pub nodes: u64,
+    pub time: Duration,
+}
+
+impl Measurement {
+    pub fn nodes_per_second(&self) -> f64 {
+        self.nodes as f64 / self.time.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+}
+
+/// Searches every position to `depth`. Each search is repeated `repeats` times and the fastest
+/// one counts, which keeps the figure stable on a busy machine.
+pub fn measure<E: Eval>(positions: &[State], eval: &E, depth: u32, repeats: u32) -> Measurement {
+    let mut total = Measurement::default();
+    for state in positions {
+        let mut time = Duration::MAX;
+        let mut nodes = 0;
+        for _ in 0..repeats.max(1) {
+            let mut position = Counted::new(*state);
+            let start = Instant::now();
+            search(&mut position, eval, depth);
+            time = time.min(start.elapsed());
+            nodes = position.nodes + 1;
+        }
+        total.nodes += nodes;
+        total.time += time;
+    }
+    total
+}
+
+/// The searches compared by `compare`.
+pub const SEARCHES: [&str; 5] = ["negamax", "pvs", "deepening", "aspiration", "table"];
+
+/// The nodes each of `SEARCHES` visits over the positions that are not over, looking `depth`
+/// moves ahead with `eval`: `negamax` and `pvs` with a full window, iterative deepening with
+/// `negamax` as `Deepening` does it, and `deepen` with `pvs` and aspiration windows, first without
+/// and then with its transposition table.
+///
+/// Without move ordering, `pvs` can visit more nodes than `negamax` at shallow depths, where
+/// the re-searches cost more than the null windows save.
+pub fn compare<E: Eval>(positions: &[State], eval: &E, depth: u32) -> [u64; SEARCHES.len()] {
+    let mut nodes = [0; SEARCHES.len()];
+    for state in positions.iter().filter(|state| !state.game_over()) {
+        let sign = if state.player1_next() { 1 } else { -1 };
+
+        let mut position = Counted::new(*state);
+        negamax(&mut position, eval, depth, -i32::MAX, i32::MAX, sign);
+        nodes[0] += position.nodes;
+
+        let mut position = Counted::new(*state);
+        pvs(&mut position, eval, depth, -i32::MAX, i32::MAX, sign);
+        nodes[1] += position.nodes;
+
+        let mut position = Counted::new(*state);
+        for depth in 1..=depth {
+            negamax(&mut position, eval, depth, -i32::MAX, i32::MAX, sign);
+        }
+        nodes[2] += position.nodes;
+
+        // `deepen` counts its depths from the moves after the root.
+        for (column, table_bits) in [(3, None), (4, Some(TABLE_BITS))] {
+            let mut position = Counted::new(*state);
+            deepen_with_table(
+                &mut position,
+                eval,
+                depth.saturating_sub(1),
+                None,
+                table_bits,
+            );
+            nodes[column] += position.nodes;
+        }
+    }
+    nodes
+}
@@ -0,0 +1,72 @@
+//! `bench [depth=7] [repeats=3]`: searches fixed positions with every eval and prints the nodes
+//! per second, for tracking performance across changes. Then prints the nodes each search
+//! algorithm visits on the same positions.
+
+use flood_it_bot::bench::{Measurement, SEARCHES, compare, measure, positions};
+use flood_it_bot::bot::{
+    Accessible, Captured, Closer, CloserColor, Eval, Quiescence, Race, Sealed, Weighted,
+};
+use flood_it_bot::game::State;
+
+fn report(
+    name: &str,
+    positions: &[State],
+    eval: &impl Eval,
+    depth: u32,
+    repeats: u32,
+) -> Measurement {
+    let measurement = measure(positions, eval, depth, repeats);
+    println!(
+        "{name:<12} {:>10} nodes {:>10.3?} {:>12.0} nps",
+        measurement.nodes,
+        measurement.time,
+        measurement.nodes_per_second()
+    );
+    measurement
+}
+
+fn report_nodes(name: &str, positions: &[State], eval: &impl Eval, depth: u32) {
+    print!("{name:<12}");
+    for nodes in compare(positions, eval, depth) {
+        print!(" {nodes:>10}");
+    }
+    println!();
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let depth = args.next().map_or(7, |arg| arg.parse().unwrap());
+    let repeats = args.next().map_or(3, |arg| arg.parse().unwrap());
+
+    let positions = positions();
+    let results = [
+        report("captured", &positions, &Captured, depth, repeats),
+        report("accessible", &positions, &Accessible, depth, repeats),
+        report("closer", &positions, &Closer, depth, repeats),
+        report("closer_color", &positions, &CloserColor, depth, repeats),
+        report("race", &positions, &Race(4), depth, repeats),
+        report("sealed", &positions, &Sealed, depth, repeats),
+        report(
+            "quiescence",
+            &positions,
+            &Quiescence::new(CloserColor),
+            depth,
+            repeats,
+        ),
+        report("weighted", &positions, &Weighted::default(), depth, repeats),
+    ];
+
+    let nodes: u64 = results.iter().map(|result| result.nodes).sum();
+    let time = results.iter().map(|result| result.time).sum();
+    let total = Measurement { nodes, time };
+    println!("{nodes} nodes {:.0} nps", total.nodes_per_second());
+
+    println!();
+    print!("{:<12}", "");
+    for search in SEARCHES {
+        print!(" {search:>10}");
+    }
+    println!();
+    report_nodes("closer_color", &positions, &CloserColor, depth);
+    report_nodes("weighted", &positions, &Weighted::default(), depth);
+}
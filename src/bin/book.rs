@@ -0,0 +1,22 @@
+//! `book <contestant> [games=1000] [seed=0] [rounds=8] [path=Book::PATH]`: builds an opening book
+//! from self-play games of a contestant and adds it to the book at `path`.
+
+use flood_it_bot::arena::find_contestant;
+use flood_it_bot::book::Book;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let name = args.next().expect("missing contestant");
+    let contestant =
+        find_contestant(&name).unwrap_or_else(|| panic!("unknown contestant `{name}`"));
+    let games = args.next().map_or(1000, |arg| arg.parse().unwrap());
+    let seed = args.next().map_or(0, |arg| arg.parse().unwrap());
+    let rounds = args.next().map_or(8, |arg| arg.parse().unwrap());
+    let path = args.next().unwrap_or_else(|| Book::PATH.to_string());
+
+    let mut book = Book::load_or_default(&path).unwrap();
+    let before = book.len();
+    book.merge(&Book::build(games, seed, rounds, contestant.make));
+    println!("positions: {before} -> {}", book.len());
+    book.save(&path).unwrap();
+}
@@ -0,0 +1,23 @@
+//! `datagen <player1> <player2> [games] [shards] [seed] [dir]`: writes self-play data shards in
+//! the format described in `flood_it_bot::datagen`.
+
+use std::path::Path;
+
+use flood_it_bot::arena::find_contestant;
+use flood_it_bot::datagen::generate;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut contestant = || {
+        let name = args.next().expect("missing contestant name");
+        find_contestant(&name).unwrap_or_else(|| panic!("unknown contestant `{name}`"))
+    };
+    let player1 = contestant();
+    let player2 = contestant();
+    let games = args.next().map_or(1000, |arg| arg.parse().unwrap());
+    let shards = args.next().map_or(16, |arg| arg.parse().unwrap());
+    let seed = args.next().map_or(0, |arg| arg.parse().unwrap());
+    let dir = args.next().unwrap_or_else(|| "data".to_string());
+
+    generate(Path::new(&dir), games, shards, seed, player1, player2).unwrap();
+}
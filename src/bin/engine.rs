@@ -0,0 +1,21 @@
+//! `engine <contestant>`: serves the protocol described in `flood_it_bot::engine` on stdin and
+//! stdout with a built in contestant. The arguments are joined with spaces, so the name does not
+//! need quoting.
+
+use std::io;
+
+use flood_it_bot::arena::find_contestant;
+use flood_it_bot::engine::serve;
+
+fn main() {
+    let name = std::env::args().skip(1).collect::<Vec<_>>().join(" ");
+    let contestant =
+        find_contestant(&name).unwrap_or_else(|| panic!("unknown contestant `{name}`"));
+    serve(
+        contestant.name,
+        contestant.make,
+        io::stdin().lock(),
+        io::stdout().lock(),
+    )
+    .unwrap();
+}
@@ -0,0 +1,30 @@
+//! `perft <seed> <depth> [moves...]`: counts the game tree from the board generated from `seed`,
+//! after the given moves, at every depth up to `depth`.
+
+use std::time::Instant;
+
+use flood_it_bot::game::{State, perft};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let seed = args.next().expect("missing seed").parse().unwrap();
+    let depth = args.next().expect("missing depth").parse().unwrap();
+
+    let mut state = State::generate(seed);
+    for arg in args {
+        state.try_play(arg.parse().unwrap()).unwrap();
+    }
+
+    for depth in 1..=depth {
+        let start = Instant::now();
+        let counts = perft(&mut state, depth);
+        println!(
+            "depth {depth}: {} leaves, {} terminal, margin {}, territory {} ({:.3?})",
+            counts.leaves,
+            counts.terminal,
+            counts.margin,
+            counts.territory,
+            start.elapsed()
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! `play <opponent> [seed] [second]`: plays a game against a contestant in the terminal.
+//!
+//! Type a color by name or letter to play it, `undo` to take back your last move, `redo` to play
+//! it again, or `quit`.
+
+use std::io::{self, BufRead, Write};
+
+use flood_it_bot::arena::make_player;
+use flood_it_bot::game::{Color, State};
+use flood_it_bot::history::GameHistory;
+
+fn parse_color(input: &str) -> Option<Color> {
+    Color::LIST
+        .into_iter()
+        .find(|color| color.name() == input || input.chars().eq([color.letter()]))
+}
+
+fn show(history: &GameHistory, human_first: bool, status: &str) {
+    let state = history.state();
+    let (human, opponent) = if human_first {
+        (state.player1, state.player2)
+    } else {
+        (state.player2, state.player1)
+    };
+    print!("{}{}", termion::clear::All, termion::cursor::Goto(1, 1));
+    print!("{}", state.colored());
+    println!(
+        "round {}, you {} cells, opponent {} cells",
+        state.round,
+        human.count_ones(),
+        opponent.count_ones()
+    );
+    if let Some(last) = history.moves().last() {
+        println!(
+            "last move {}, captured {} cells",
+            last.color,
+            last.captured.count_ones()
+        );
+    }
+    println!("{status}");
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut opponent = make_player(&args.next().expect("missing opponent")).unwrap();
+    let seed: u64 = args
+        .next()
+        .map_or_else(rand::random, |arg| arg.parse().unwrap());
+    let human_first = args.next().as_deref() != Some("second");
+
+    let mut history = GameHistory::new(State::generate(seed));
+    let mut lines = io::stdin().lock().lines();
+    let human_next = |history: &GameHistory| history.state().player1_next() == human_first;
+
+    let mut status = String::new();
+    loop {
+        if !history.state().game_over() && !human_next(&history) {
+            let played = opponent
+                .try_play(history.state(), None)
+                .map_err(|error| error.to_string())
+                .and_then(|color| history.play(color).map_err(|error| error.to_string()));
+            if let Err(error) = played {
+                println!("the opponent forfeits: {error}");
+                break;
+            }
+            continue;
+        }
+
+        show(&history, human_first, &status);
+        status.clear();
+        if history.state().game_over() {
+            let margin = history.state().final_margin();
+            let margin = if human_first { margin } else { -margin };
+            println!("game over, margin {margin}");
+        } else {
+            let moves: Vec<_> = history.state().valid_moves().map(Color::name).collect();
+            println!("your move ({})", moves.join(", "));
+        }
+        print!("> ");
+        io::stdout().flush().unwrap();
+
+        let Some(line) = lines.next() else { break };
+        match line.unwrap().trim() {
+            "quit" => break,
+            "undo" => while history.undo().is_some() && !human_next(&history) {},
+            "redo" => while history.redo().is_some() && !human_next(&history) {},
+            input => match parse_color(input).map(|color| history.play(color)) {
+                Some(Ok(_)) => {}
+                Some(Err(error)) => status = error.to_string(),
+                None => status = format!("unknown command `{input}`"),
+            },
+        }
+    }
+}
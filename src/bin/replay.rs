@@ -0,0 +1,58 @@
+//! `replay <seed> <color>...`: steps through a game given by its seed and moves.
+//!
+//! Press enter or type `next` to see the next move, `back` to go back one, or `quit`.
+
+use std::io::{self, BufRead, Write};
+
+use flood_it_bot::history::GameHistory;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let seed: u64 = args.next().expect("missing seed").parse().unwrap();
+    let colors: Vec<_> = args.map(|arg| arg.parse().unwrap()).collect();
+
+    let mut history = GameHistory::replay(seed, colors).unwrap();
+    for (round, step) in history.moves().iter().enumerate() {
+        let player = if round % 2 == 0 { 1 } else { 2 };
+        println!(
+            "{round:>3}: player {player} plays {:<6} and captures {:>3} cells",
+            step.color,
+            step.captured.count_ones()
+        );
+    }
+    while history.undo().is_some() {}
+
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        let state = history.state();
+        print!("{}", state.colored());
+        match history.moves().last() {
+            Some(last) => println!(
+                "round {}: {} captured {} cells",
+                state.round,
+                last.color,
+                last.captured.count_ones()
+            ),
+            None => println!("round 0"),
+        }
+        if state.game_over() {
+            println!("game over, margin {}", state.final_margin());
+        }
+        print!("> ");
+        io::stdout().flush().unwrap();
+
+        let Some(line) = lines.next() else { break };
+        let stepped = match line.unwrap().trim() {
+            "" | "next" => history.redo(),
+            "back" => history.undo(),
+            "quit" => break,
+            input => {
+                println!("unknown command `{input}`");
+                continue;
+            }
+        };
+        if stepped.is_none() {
+            println!("no more moves");
+        }
+    }
+}
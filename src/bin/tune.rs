@@ -0,0 +1,22 @@
+//! `tune [games] [seed] [path]`: fits the `Weighted` eval to self-play games and saves it.
+
+use flood_it_bot::bot::{Negamax, Weighted};
+use flood_it_bot::tune::{collect, error, fit};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let games = args.next().map_or(200, |arg| arg.parse().unwrap());
+    let seed = args.next().map_or(0, |arg| arg.parse().unwrap());
+    let path = args.next().unwrap_or_else(|| Weighted::PATH.to_string());
+
+    let before = Weighted::load_or_default(&path).unwrap();
+    let samples = collect(games, seed, || Box::new(Negamax(before, 2)));
+    let after = fit(&samples);
+
+    println!("positions: {}", samples.len());
+    println!("error before: {:.3}", error(&before, &samples));
+    println!("error after:  {:.3}", error(&after, &samples));
+    print!("{after}");
+
+    after.save(&path).unwrap();
+}
@@ -0,0 +1,37 @@
+//! `versus <player1> <player2> [games] [seed] [time control]`: plays both sides of `games` seeds.
+//!
+//! Players are contestant names, `engine:<command line>` for external engines or `book:<player>`
+//! for a player that starts from the opening book.
+
+use flood_it_bot::arena::{ContestantStats, MatchResult, make_player, play_timed_game};
+use flood_it_bot::clock::TimeControl;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut player1 = make_player(&args.next().expect("missing player")).unwrap();
+    let mut player2 = make_player(&args.next().expect("missing player")).unwrap();
+    let games: u64 = args.next().map_or(10, |arg| arg.parse().unwrap());
+    let seed: u64 = args
+        .next()
+        .map_or_else(rand::random, |arg| arg.parse().unwrap());
+    let time_control: Option<TimeControl> = args.next().map(|arg| arg.parse().unwrap());
+
+    let mut stats = [ContestantStats::new(); 2];
+    for seed in seed..seed + games {
+        let first = play_timed_game(seed, player1.as_mut(), player2.as_mut(), time_control);
+        let second = play_timed_game(seed, player2.as_mut(), player1.as_mut(), time_control);
+        let result = MatchResult::new(0, 1, first, second);
+        result.update(&mut stats);
+        println!("seed {seed}: margin {}", result.margin);
+    }
+
+    let [stats, _] = stats;
+    println!(
+        "wins {}, losses {}, draws {}, forfeits {}, average margin {:.1}",
+        stats.wins,
+        stats.losses,
+        stats.draws,
+        stats.forfeits,
+        stats.avg_margin()
+    );
+}
@@ -0,0 +1,358 @@
+//! An opening book: statistics of the moves played from early positions, keyed by
+//! `State::canonical_hash` so that equivalent positions share them.
+//!
+//! Books are saved as text, one line per position and move:
+//! `<hash, 16 hex digits> <canonical color> <games> <total margin>`. Colors are the ones of
+//! `State::canonical`, and margins are from the point of view of the player making the move.
+
+use core::fmt;
+use core::str::FromStr;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::bot::Player;
+use crate::clock::Clock;
+use crate::datagen::{parallel, self_play};
+use crate::game::{Color, State};
+
+/// How a move did in the games it was played in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub games: u32,
+    /// The sum of the final margins, for the player who made the move.
+    pub margin: i64,
+}
+
+impl Stats {
+    pub fn average(&self) -> f64 {
+        self.margin as f64 / f64::from(self.games.max(1))
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Book {
+    /// The stats of each move, indexed by canonical color.
+    positions: HashMap<u64, [Stats; 8]>,
+}
+
+impl Book {
+    /// Where `book` saves the book it builds, and where `book:` players look for it.
+    pub const PATH: &str = "book.txt";
+    /// How many games a move must have been played in for `book:` players to trust it.
+    pub const MIN_GAMES: u32 = 4;
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Like `load`, but an empty book if there is no file at `path` yet.
+    pub fn load_or_default(path: impl AsRef<Path>) -> io::Result<Self> {
+        match Self::load(path) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            result => result,
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// The number of positions in the book.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Records that `color` was played from `state` in a game that the mover won by `margin`.
+    pub fn add(&mut self, state: &State, color: Color, margin: i32) {
+        let label = state.canonical_colors()[color as usize];
+        let entry = &mut self.positions.entry(state.canonical_hash()).or_default()[label as usize];
+        entry.games += 1;
+        entry.margin += i64::from(margin);
+    }
+
+    /// Adds the stats of `other` to this book.
+    pub fn merge(&mut self, other: &Book) {
+        for (hash, moves) in &other.positions {
+            let entry = self.positions.entry(*hash).or_default();
+            for (stats, other) in entry.iter_mut().zip(moves) {
+                stats.games += other.games;
+                stats.margin += other.margin;
+            }
+        }
+    }
+
+    /// The stats of the valid moves of `state` that were played.
+    pub fn moves(&self, state: &State) -> impl Iterator<Item = (Color, Stats)> {
+        let moves = self.positions.get(&state.canonical_hash());
+        let labels = state.canonical_colors();
+        state.valid_moves().filter_map(move |color| {
+            let played = moves?[labels[color as usize] as usize];
+            (played.games > 0).then_some((color, played))
+        })
+    }
+
+    /// The move with the best average margin among those played at least `min_games` times.
+    pub fn best(&self, state: &State, min_games: u32) -> Option<Color> {
+        self.moves(state)
+            .filter(|(_, stats)| stats.games >= min_games)
+            .max_by(|(_, a), (_, b)| a.average().total_cmp(&b.average()))
+            .map(|(color, _)| color)
+    }
+
+    /// Plays `games` self-play games on seeds `seed..seed + games` and adds the moves made
+    /// before round `rounds`.
+    pub fn build(games: u64, seed: u64, rounds: u32, make: fn() -> Box<dyn Player>) -> Self {
+        let games = parallel(games, |game| {
+            let mut samples = self_play(seed + game, make(), make());
+            samples.retain(|sample| sample.record.state.round < rounds);
+            samples
+        });
+        let mut book = Book::default();
+        for sample in games.into_iter().flatten() {
+            let record = sample.record;
+            let margin = if record.state.player1_next() {
+                sample.margin
+            } else {
+                -sample.margin
+            };
+            book.add(&record.state, record.color, margin);
+        }
+        book
+    }
+}
+
+impl fmt::Display for Book {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut hashes: Vec<_> = self.positions.keys().collect();
+        hashes.sort_unstable();
+        for hash in hashes {
+            for (color, stats) in Color::LIST.iter().zip(&self.positions[hash]) {
+                if stats.games > 0 {
+                    writeln!(f, "{hash:016x} {color} {} {}", stats.games, stats.margin)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Book {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut book = Book::default();
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let malformed = || invalid(format!("malformed line `{line}`"));
+            let mut fields = line.split_whitespace();
+            let mut next = || fields.next().ok_or_else(malformed);
+            let hash = u64::from_str_radix(next()?, 16).map_err(|_| malformed())?;
+            let color: Color = next()?.parse().map_err(invalid)?;
+            let games = next()?.parse().map_err(|_| malformed())?;
+            let margin = next()?.parse().map_err(|_| malformed())?;
+            book.positions.entry(hash).or_default()[color as usize] = Stats { games, margin };
+        }
+        Ok(book)
+    }
+}
+
+/// Plays the best book move while there is one, and lets `inner` play otherwise.
+pub struct Booked<P> {
+    pub book: Arc<Book>,
+    pub inner: P,
+    /// How many games a move must have been played in to be trusted.
+    pub min_games: u32,
+}
+
+impl<P: Player> Player for Booked<P> {
+    fn play(&mut self, state: &State) -> Color {
+        self.book
+            .best(state, self.min_games)
+            .unwrap_or_else(|| self.inner.play(state))
+    }
+
+    fn play_scored(&mut self, state: &State) -> (Color, Option<i32>) {
+        match self.book.best(state, self.min_games) {
+            Some(color) => (color, None),
+            None => self.inner.play_scored(state),
+        }
+    }
+
+    fn play_timed(&mut self, state: &State, clock: &Clock) -> Color {
+        self.book
+            .best(state, self.min_games)
+            .unwrap_or_else(|| self.inner.play_timed(state, clock))
+    }
+
+    fn try_play(&mut self, state: &State, clock: Option<&Clock>) -> io::Result<Color> {
+        match self.book.best(state, self.min_games) {
+            Some(color) => Ok(color),
+            None => self.inner.try_play(state, clock),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::Greedy;
+
+    /// The early positions of a few greedy games.
+    fn states() -> Vec<State> {
+        let mut states = Vec::new();
+        for seed in 0..20 {
+            let mut state = State::generate(seed);
+            while state.round < 6 && !state.game_over() {
+                states.push(state);
+                state.play(Greedy.play(&state));
+            }
+        }
+        states
+    }
+
+    /// A valid move other than `color`, if there is one.
+    fn other_move(state: &State, color: Color) -> Option<Color> {
+        state.valid_moves().find(|&other| other != color)
+    }
+
+    #[test]
+    fn best_move() {
+        for state in states() {
+            let mut book = Book::default();
+            let good = Greedy.play(&state);
+            let Some(bad) = other_move(&state, good) else {
+                continue;
+            };
+            assert_eq!(book.best(&state, 0), None);
+
+            book.add(&state, bad, -10);
+            book.add(&state, good, 3);
+            book.add(&state, good, 5);
+            assert_eq!(book.best(&state, 1), Some(good));
+            assert_eq!(book.best(&state, 2), Some(good));
+            assert_eq!(book.best(&state, 3), None);
+            assert_eq!(book.moves(&state).count(), 2);
+            assert!(book.moves(&state).any(|(color, stats)| {
+                color == good
+                    && stats
+                        == Stats {
+                            games: 2,
+                            margin: 8,
+                        }
+            }));
+
+            book.add(&state, bad, 30);
+            assert_eq!(book.best(&state, 2), Some(bad));
+        }
+    }
+
+    #[test]
+    fn merge_adds_stats() {
+        let states = states();
+        let (mut all, mut first, mut second) = (Book::default(), Book::default(), Book::default());
+        for (i, state) in states.iter().enumerate() {
+            let color = Greedy.play(state);
+            let margin = i as i32 % 7 - 3;
+            all.add(state, color, margin);
+            if i % 3 == 0 { &mut first } else { &mut second }.add(state, color, margin);
+        }
+        first.merge(&second);
+        assert_eq!(first.positions, all.positions);
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let mut book = Book::default();
+        for (i, state) in states().iter().enumerate() {
+            book.add(state, Greedy.play(state), i as i32 % 11 - 5);
+        }
+        let text = book.to_string();
+        let parsed: Book = text.parse().unwrap();
+        assert_eq!(parsed.positions, book.positions);
+        assert_eq!(parsed.to_string(), text);
+
+        let commented: Book = format!("# a comment\n\n{text}").parse().unwrap();
+        assert_eq!(commented.positions, book.positions);
+        for line in ["0 red 1", "xyz red 1 2", "0 black 1 2", "0 red one 2"] {
+            let error = line.parse::<Book>().unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn twins_share_moves() {
+        let mut labels = Color::LIST;
+        labels.rotate_left(3);
+        let relabel = |color: Option<Color>| color.map(|color| labels[color as usize]);
+        for state in states() {
+            let mut twin = state;
+            for (color, mask) in state.colors.into_iter().enumerate() {
+                twin.colors[labels[color] as usize] = mask;
+            }
+            twin.player1_last_move = relabel(state.player1_last_move);
+            twin.player2_last_move = relabel(state.player2_last_move);
+            assert_eq!(twin.canonical_hash(), state.canonical_hash());
+
+            let mut book = Book::default();
+            let color = Greedy.play(&state);
+            book.add(&state, color, 1);
+            assert_eq!(book.best(&twin, 1), Some(labels[color as usize]));
+        }
+    }
+
+    /// A player that always plays the same move, and counts how often it was asked to.
+    struct Counting(Color, u32);
+
+    impl Player for Counting {
+        fn play(&mut self, _: &State) -> Color {
+            self.1 += 1;
+            self.0
+        }
+    }
+
+    #[test]
+    fn booked_falls_back_to_inner() {
+        for state in states() {
+            let inner = state.valid_moves().next().unwrap();
+            let Some(booked) = other_move(&state, inner) else {
+                continue;
+            };
+            let mut book = Book::default();
+            book.add(&state, booked, 1);
+            let mut player = Booked {
+                book: Arc::new(book),
+                inner: Counting(inner, 0),
+                min_games: 1,
+            };
+            assert_eq!(player.play(&state), booked);
+            assert_eq!(player.try_play(&state, None).unwrap(), booked);
+            assert_eq!(player.inner.1, 0);
+
+            player.min_games = 2;
+            assert_eq!(player.play(&state), inner);
+            assert_eq!(player.play_scored(&state), (inner, None));
+            assert_eq!(player.inner.1, 2);
+        }
+    }
+
+    #[test]
+    fn missing_book_is_empty() {
+        let path = std::env::temp_dir().join(format!("flood-it-{}-book", std::process::id()));
+        assert!(Book::load_or_default(&path).unwrap().is_empty());
+        fs::write(&path, "not a book").unwrap();
+        let error = Book::load_or_default(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
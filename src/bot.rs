@@ -1,29 +1,215 @@
 use core::cmp::max;
+use core::fmt;
+use core::str::FromStr;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Instant;
 
-use crate::game::{Color, State};
+use crate::clock::Clock;
+use crate::game::{Color, Position, ROUND_LIMIT, State};
 
 const INFINITY: i32 = 1_000_000_000;
 
-pub fn negamax<E: Eval>(
-    state: &mut State,
+/// Fail-hard alpha-beta search to `depth`. Returns the value for the player with `sign`, where 1
+/// is player 1 and -1 player 2.
+pub fn negamax<P: Position, E: Eval>(
+    position: &mut P,
     eval: &E,
     depth: u32,
     mut alpha: i32,
     beta: i32,
     sign: i32,
 ) -> i32 {
-    if state.game_over() {
-        return sign * (INFINITY + state.final_margin() + depth as i32);
-    } else if depth == 0 {
-        return sign * eval.eval(state);
+    let state = position.state();
+    if let Some(value) = leaf(state, eval, depth) {
+        return sign * value;
+    }
+
+    let mut max_value = i32::MIN;
+    let checkpoint = position.checkpoint();
+    for color in state.valid_moves() {
+        position.play(color);
+        let value = -negamax(position, eval, depth - 1, -beta, -alpha, -sign);
+        position.restore(checkpoint);
+
+        max_value = max(max_value, value);
+        alpha = max(alpha, value);
+        if alpha >= beta {
+            break;
+        }
+    }
+    max_value
+}
+
+/// The value of a position for player 1 if the search stops there: final once the winner is
+/// decided, and otherwise `eval` kept within the margins the sealed regions still allow.
+///
+/// The winner is decided when the game is over, or when one player has sealed off more cells than
+/// are left to contest. The search then stops even with depth left, scoring the sealed cells only.
+/// Decided values are symmetric between the players, since the table shares them between a
+/// position and its twin with the players swapped, and favor the quickest win and slowest loss.
+fn leaf<E: Eval>(state: &State, eval: &E, depth: u32) -> Option<i32> {
+    let (player1, player2, contested) = state.sealed();
+    let secured = player1.count_ones() as i32 - player2.count_ones() as i32;
+    let open = contested.count_ones() as i32;
+    if secured.abs() > open || open == 0 || state.round == ROUND_LIMIT {
+        return Some(secured.signum() * (INFINITY + depth as i32) + secured);
+    } else if depth > 0 {
+        return None;
+    }
+
+    let value = eval.eval(state);
+    Some(eval.cell_value().map_or(value, |cell| {
+        value.clamp(cell * (secured - open), cell * (secured + open))
+    }))
+}
+
+/// Stops a search once a point in time has passed.
+struct Deadline {
+    at: Option<Instant>,
+    nodes: u32,
+    expired: bool,
+}
+
+impl Deadline {
+    /// Checking the time is slow compared to visiting a node, so only do it every so often.
+    const INTERVAL: u32 = 256;
+
+    fn new(at: Option<Instant>) -> Self {
+        Self {
+            at,
+            nodes: 0,
+            expired: false,
+        }
+    }
+
+    fn expired(&mut self) -> bool {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(Self::INTERVAL) {
+            self.expired |= self.at.is_some_and(|at| Instant::now() >= at);
+        }
+        self.expired
+    }
+}
+
+/// What a stored value says about the true one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    /// The search failed high: the value is at least this.
+    Lower,
+    /// The search failed low: the value is at most this.
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    key: u64,
+    depth: u32,
+    value: i32,
+    bound: Bound,
+}
+
+/// Search results keyed by `State::canonical_hash` for symmetric evals, so that transpositions
+/// and positions equivalent under the board symmetry share entries, and by
+/// `State::position_hash` for the others, so that only transpositions do. Each slot keeps the
+/// latest result.
+struct Table {
+    entries: Vec<Option<Entry>>,
+    symmetric: bool,
+}
+
+impl Table {
+    /// Nodes closer to the leaves than this are cheaper to search than to hash.
+    const MIN_DEPTH: u32 = 2;
+
+    /// A table of `1 << bits` entries, or one that stores nothing if `bits` is `None`, for
+    /// searches with an eval that is `symmetric` or not.
+    fn new(bits: Option<u32>, symmetric: bool) -> Self {
+        Self {
+            entries: bits.map_or_else(Vec::new, |bits| vec![None; 1 << bits]),
+            symmetric,
+        }
+    }
+
+    /// The key of `state` when searched to `depth`, if its value may be shared. Both hashes
+    /// leave out the round, so positions that can reach `ROUND_LIMIT` are never stored.
+    fn key(&self, state: &State, depth: u32) -> Option<u64> {
+        (!self.entries.is_empty() && depth >= Self::MIN_DEPTH && state.round + depth < ROUND_LIMIT)
+            .then(|| {
+                if self.symmetric {
+                    state.canonical_hash()
+                } else {
+                    state.position_hash()
+                }
+            })
+    }
+
+    fn slot(&self, key: u64) -> usize {
+        key as usize & (self.entries.len() - 1)
+    }
+
+    /// A stored value for the player to move that settles a search to `depth` with the window
+    /// `alpha..beta`. Only searches to the same depth count, so that values stay exact.
+    fn probe(&self, key: u64, depth: u32, alpha: i32, beta: i32) -> Option<i32> {
+        let entry = self.entries[self.slot(key)]?;
+        let settled = match entry.bound {
+            Bound::Exact => true,
+            Bound::Lower => entry.value >= beta,
+            Bound::Upper => entry.value <= alpha,
+        };
+        (entry.key == key && entry.depth == depth && settled).then_some(entry.value)
+    }
+
+    /// Stores the `value` a search to `depth` with the window `alpha..beta` found.
+    fn store(&mut self, key: u64, depth: u32, alpha: i32, beta: i32, value: i32) {
+        let bound = if value <= alpha {
+            Bound::Upper
+        } else if value >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        let slot = self.slot(key);
+        self.entries[slot] = Some(Entry {
+            key,
+            depth,
+            value,
+            bound,
+        });
+    }
+}
+
+/// What a search carries from node to node: when to stop, and what it has found so far.
+struct Search {
+    deadline: Deadline,
+    table: Table,
+}
+
+/// Like `negamax`, but gives up and returns `None` once `deadline` expires.
+fn negamax_until<E: Eval>(
+    state: &mut State,
+    eval: &E,
+    depth: u32,
+    mut alpha: i32,
+    beta: i32,
+    sign: i32,
+    deadline: &mut Deadline,
+) -> Option<i32> {
+    if deadline.expired() {
+        return None;
+    } else if let Some(value) = leaf(state, eval, depth) {
+        return Some(sign * value);
     }
 
     let mut max_value = i32::MIN;
     let checkpoint = state.checkpoint();
     for color in state.valid_moves() {
         state.play(color);
-        let value = -negamax(state, eval, depth - 1, -beta, -alpha, -sign);
+        let value = negamax_until(state, eval, depth - 1, -beta, -alpha, -sign, deadline);
         state.restore(checkpoint);
+        let value = -value?;
 
         max_value = max(max_value, value);
         alpha = max(alpha, value);
@@ -31,13 +217,307 @@ pub fn negamax<E: Eval>(
             break;
         }
     }
-    max_value
+    Some(max_value)
 }
 
+/// Half the width of the first aspiration window in `deepen`, in cells.
+const ASPIRATION: i32 = 2;
+/// The transposition table of `deepen` has `1 << TABLE_BITS` entries.
+pub(crate) const TABLE_BITS: u32 = 16;
+
+/// Principal variation search. Like `negamax`, but only the first move gets the full window: the
+/// others are searched with a null window to show they are no better, and searched again with
+/// the full window if they are. Returns the same value as `negamax` inside the window.
+pub fn pvs<P: Position, E: Eval>(
+    position: &mut P,
+    eval: &E,
+    depth: u32,
+    alpha: i32,
+    beta: i32,
+    sign: i32,
+) -> i32 {
+    let mut search = Search {
+        deadline: Deadline::new(None),
+        table: Table::new(None, false),
+    };
+    pvs_until(position, eval, depth, alpha, beta, sign, &mut search).unwrap()
+}
+
+/// Like `pvs`, but gives up and returns `None` once the deadline expires, and shares results
+/// through the table.
+fn pvs_until<P: Position, E: Eval>(
+    position: &mut P,
+    eval: &E,
+    depth: u32,
+    mut alpha: i32,
+    beta: i32,
+    sign: i32,
+    search: &mut Search,
+) -> Option<i32> {
+    if search.deadline.expired() {
+        return None;
+    }
+    let state = position.state();
+    if let Some(value) = leaf(state, eval, depth) {
+        return Some(sign * value);
+    }
+    let key = search.table.key(state, depth);
+    if let Some(key) = key
+        && let Some(value) = search.table.probe(key, depth, alpha, beta)
+    {
+        return Some(value);
+    }
+    let window = (alpha, beta);
+
+    let mut max_value = i32::MIN;
+    let checkpoint = position.checkpoint();
+    for color in state.valid_moves() {
+        position.play(color);
+        let value = pvs_child(
+            position,
+            eval,
+            depth,
+            alpha,
+            beta,
+            max_value == i32::MIN,
+            search,
+        );
+        position.restore(checkpoint);
+        let value = value?;
+
+        max_value = max(max_value, value);
+        alpha = max(alpha, value);
+        if alpha >= beta {
+            break;
+        }
+    }
+    if let Some(key) = key {
+        search
+            .table
+            .store(key, depth, window.0, window.1, max_value);
+    }
+    Some(max_value)
+}
+
+/// The value of the move just played on `position` for the player who played it, searched with
+/// the full window if it is the `first` and otherwise with a null window first.
+fn pvs_child<P: Position, E: Eval>(
+    position: &mut P,
+    eval: &E,
+    depth: u32,
+    alpha: i32,
+    beta: i32,
+    first: bool,
+    search: &mut Search,
+) -> Option<i32> {
+    let sign = if position.state().player1_next() {
+        1
+    } else {
+        -1
+    };
+    if !first {
+        let value = -pvs_until(position, eval, depth - 1, -alpha - 1, -alpha, sign, search)?;
+        if value <= alpha || value >= beta {
+            return Some(value);
+        }
+    }
+    Some(-pvs_until(
+        position,
+        eval,
+        depth - 1,
+        -beta,
+        -alpha,
+        sign,
+        search,
+    )?)
+}
+
+/// Searches every move at the root with `pvs`, trying `previous` first. Returns the best move
+/// and its value for the player to move, or `None` if the deadline expires.
+fn pvs_root<P: Position, E: Eval>(
+    position: &mut P,
+    eval: &E,
+    depth: u32,
+    mut alpha: i32,
+    beta: i32,
+    previous: Option<Color>,
+    search: &mut Search,
+) -> Option<(Color, i32)> {
+    let mut moves: Vec<_> = position.state().valid_moves().collect();
+    moves.sort_by_key(|&color| Some(color) != previous);
+
+    let mut best: Option<(Color, i32)> = None;
+    let checkpoint = position.checkpoint();
+    for color in moves {
+        position.play(color);
+        let value = pvs_child(
+            position,
+            eval,
+            depth + 1,
+            alpha,
+            beta,
+            best.is_none(),
+            search,
+        );
+        position.restore(checkpoint);
+        let value = value?;
+
+        if best.is_none_or(|(_, best)| value > best) {
+            best = Some((color, value));
+        }
+        alpha = max(alpha, value);
+        if alpha >= beta {
+            break;
+        }
+    }
+    best
+}
+
+/// Iterative deepening with `pvs` up to `depth`. Each depth after the first is searched with an
+/// aspiration window around the previous value, which is widened whenever the value falls
+/// outside it. The depths share a transposition table. Stops early once `deadline` passes,
+/// returning the deepest complete result.
+pub fn deepen<P: Position, E: Eval>(
+    position: &mut P,
+    eval: &E,
+    depth: u32,
+    deadline: Option<Instant>,
+) -> (Color, i32) {
+    deepen_with_table(position, eval, depth, deadline, Some(TABLE_BITS))
+}
+
+/// Like `deepen`, but with a table of `1 << table_bits` entries, or none if `table_bits` is
+/// `None`, to measure what the table saves.
+pub(crate) fn deepen_with_table<P: Position, E: Eval>(
+    position: &mut P,
+    eval: &E,
+    depth: u32,
+    deadline: Option<Instant>,
+    table_bits: Option<u32>,
+) -> (Color, i32) {
+    let mut search = Search {
+        deadline: Deadline::new(None),
+        table: Table::new(table_bits, eval.symmetric()),
+    };
+    let mut best: Option<(Color, i32)> = None;
+    for depth in 0..=depth {
+        // The shallowest search always completes so that there is a move to play.
+        search.deadline = Deadline::new(if depth == 0 { None } else { deadline });
+        let mut delta = ASPIRATION * eval.cell_value().unwrap_or(1);
+        let (mut alpha, mut beta) = best.map_or((-i32::MAX, i32::MAX), |(_, value)| {
+            (
+                value.saturating_sub(delta).max(-i32::MAX),
+                value.saturating_add(delta),
+            )
+        });
+        loop {
+            let previous = best.map(|(color, _)| color);
+            let Some((color, value)) =
+                pvs_root(position, eval, depth, alpha, beta, previous, &mut search)
+            else {
+                return best.unwrap();
+            };
+
+            delta = delta.saturating_mul(4);
+            if value <= alpha && alpha > -i32::MAX {
+                alpha = value.saturating_sub(delta).max(-i32::MAX);
+            } else if value >= beta && beta < i32::MAX {
+                beta = value.saturating_add(delta);
+            } else {
+                best = Some((color, value));
+                break;
+            }
+        }
+    }
+    best.unwrap()
+}
+
+/// Something that chooses moves.
 pub trait Player {
     fn play(&mut self, state: &State) -> Color;
+
+    /// Like `play`, but also returns the search score from the perspective of the player to
+    /// move, for players that have one.
+    fn play_scored(&mut self, state: &State) -> (Color, Option<i32>) {
+        (self.play(state), None)
+    }
+
+    /// Like `play`, but under a time control. Players that ignore `clock` risk flagging.
+    fn play_timed(&mut self, state: &State, clock: &Clock) -> Color {
+        let _ = clock;
+        self.play(state)
+    }
+
+    /// `play_timed` if there is a clock and `play` otherwise, for players that can fail, such as
+    /// engines in other processes. A player that fails forfeits the game.
+    fn try_play(&mut self, state: &State, clock: Option<&Clock>) -> io::Result<Color> {
+        Ok(match clock {
+            Some(clock) => self.play_timed(state, clock),
+            None => self.play(state),
+        })
+    }
+}
+
+impl<P: Player + ?Sized> Player for Box<P> {
+    fn play(&mut self, state: &State) -> Color {
+        self.as_mut().play(state)
+    }
+
+    fn play_scored(&mut self, state: &State) -> (Color, Option<i32>) {
+        self.as_mut().play_scored(state)
+    }
+
+    fn play_timed(&mut self, state: &State, clock: &Clock) -> Color {
+        self.as_mut().play_timed(state, clock)
+    }
+
+    fn try_play(&mut self, state: &State, clock: Option<&Clock>) -> io::Result<Color> {
+        self.as_mut().try_play(state, clock)
+    }
+}
+
+/// A move made by a player, together with the state it was made from.
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Record {
+    pub state: State,
+    pub color: Color,
+    pub score: Option<i32>,
+}
+
+/// Wraps a player and records every move it makes.
+pub struct Recorder<'a, P> {
+    pub inner: P,
+    pub records: &'a mut Vec<Record>,
 }
 
+impl<P: Player> Player for Recorder<'_, P> {
+    fn play(&mut self, state: &State) -> Color {
+        self.play_scored(state).0
+    }
+
+    fn play_scored(&mut self, state: &State) -> (Color, Option<i32>) {
+        let (color, score) = self.inner.play_scored(state);
+        self.records.push(Record {
+            state: *state,
+            color,
+            score,
+        });
+        (color, score)
+    }
+
+    fn play_timed(&mut self, state: &State, clock: &Clock) -> Color {
+        let color = self.inner.play_timed(state, clock);
+        self.records.push(Record {
+            state: *state,
+            color,
+            score: None,
+        });
+        color
+    }
+}
+
+/// Plays the move that captures the most cells.
 pub struct Greedy;
 
 impl Player for Greedy {
@@ -57,31 +537,130 @@ impl Player for Greedy {
     }
 }
 
+/// A static evaluation of a position from player 1's perspective.
 pub trait Eval {
     fn eval(&self, state: &State) -> i32;
+
+    /// What one cell of final margin is worth, for evals that estimate the final margin.
+    fn cell_value(&self) -> Option<i32> {
+        None
+    }
+
+    /// Whether the eval gives the player to move the same value in every position that
+    /// `State::canonical` maps to the same form: with the board rotated, the players swapped or
+    /// the colors relabeled. Searches only share values between such twins for symmetric evals.
+    fn symmetric(&self) -> bool {
+        false
+    }
 }
 
+/// Searches every move to a fixed depth with `negamax`.
 #[derive(Default, Clone, Copy, Debug)]
 pub struct Negamax<E>(pub E, pub u32);
 
 impl<E: Eval> Player for Negamax<E> {
     fn play(&mut self, state: &State) -> Color {
+        self.play_scored(state).0
+    }
+
+    fn play_scored(&mut self, state: &State) -> (Color, Option<i32>) {
         let mut state = *state;
         let checkpoint = state.checkpoint();
         let sign = if state.player1_next() { 1 } else { -1 };
 
-        state
+        let (color, value) = state
             .valid_moves()
-            .max_by_key(|color| {
-                state.play(*color);
+            .map(|color| {
+                state.play(color);
                 let value = -negamax(&mut state, &self.0, self.1, -INFINITY, INFINITY, -sign);
                 state.restore(checkpoint);
-                value
+                (color, value)
             })
-            .unwrap()
+            .max_by_key(|(_, value)| *value)
+            .unwrap();
+        (color, Some(value))
     }
 }
 
+/// Iterative deepening negamax. Searches up to the given depth, or for as long as the clock
+/// allows when playing under a time control.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Deepening<E>(pub E, pub u32);
+
+impl<E: Eval> Deepening<E> {
+    fn search(&self, state: &State, deadline: Option<Instant>) -> (Color, i32) {
+        let mut state = *state;
+        let checkpoint = state.checkpoint();
+        let sign = if state.player1_next() { 1 } else { -1 };
+
+        let mut best = None;
+        'deepening: for depth in 0..=self.1 {
+            // The shallowest search always completes so that there is a move to play.
+            let mut deadline = Deadline::new(if depth == 0 { None } else { deadline });
+            let mut results = Vec::new();
+            for color in state.valid_moves() {
+                state.play(color);
+                let value = negamax_until(
+                    &mut state,
+                    &self.0,
+                    depth,
+                    -INFINITY,
+                    INFINITY,
+                    -sign,
+                    &mut deadline,
+                );
+                state.restore(checkpoint);
+                let Some(value) = value else {
+                    break 'deepening;
+                };
+                results.push((color, -value));
+            }
+            best = results.into_iter().max_by_key(|(_, value)| *value);
+        }
+        best.unwrap()
+    }
+}
+
+impl<E: Eval> Player for Deepening<E> {
+    fn play(&mut self, state: &State) -> Color {
+        self.search(state, None).0
+    }
+
+    fn play_scored(&mut self, state: &State) -> (Color, Option<i32>) {
+        let (color, value) = self.search(state, None);
+        (color, Some(value))
+    }
+
+    fn play_timed(&mut self, state: &State, clock: &Clock) -> Color {
+        let deadline = Instant::now() + clock.budget(state);
+        self.search(state, Some(deadline)).0
+    }
+}
+
+/// Like `Deepening`, but searches with `deepen`, using `pvs` and aspiration windows. The
+/// transposition table only shares values between twin positions if the eval is symmetric.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Pvs<E>(pub E, pub u32);
+
+impl<E: Eval> Player for Pvs<E> {
+    fn play(&mut self, state: &State) -> Color {
+        self.play_scored(state).0
+    }
+
+    fn play_scored(&mut self, state: &State) -> (Color, Option<i32>) {
+        let mut state = *state;
+        let (color, value) = deepen(&mut state, &self.0, self.1, None);
+        (color, Some(value))
+    }
+
+    fn play_timed(&mut self, state: &State, clock: &Clock) -> Color {
+        let deadline = Instant::now() + clock.budget(state);
+        let mut state = *state;
+        deepen(&mut state, &self.0, self.1, Some(deadline)).0
+    }
+}
+
+/// The difference in territory.
 #[derive(Default, Clone, Copy, Debug)]
 pub struct Captured;
 
@@ -89,8 +668,13 @@ impl Eval for Captured {
     fn eval(&self, state: &State) -> i32 {
         state.player1.count_ones() as i32 - state.player2.count_ones() as i32
     }
+
+    fn symmetric(&self) -> bool {
+        true
+    }
 }
 
+/// The difference in cells each player could still reach.
 #[derive(Default, Clone, Copy, Debug)]
 pub struct Accessible;
 
@@ -101,8 +685,13 @@ impl Eval for Accessible {
         let player2_accessible = state.player2.bfs(accessible);
         player1_accessible.count_ones() as i32 - player2_accessible.count_ones() as i32
     }
+
+    fn symmetric(&self) -> bool {
+        true
+    }
 }
 
+/// The difference in cells each player is closer to.
 #[derive(Default, Clone, Copy, Debug)]
 pub struct Closer;
 
@@ -111,8 +700,17 @@ impl Eval for Closer {
         let (player1_closer, player2_closer) = state.player1.closer(state.player2, state.walls);
         player1_closer.count_ones() as i32 - player2_closer.count_ones() as i32
     }
+
+    fn cell_value(&self) -> Option<i32> {
+        Some(1)
+    }
+
+    fn symmetric(&self) -> bool {
+        true
+    }
 }
 
+/// The difference in cells each player is fewer moves away from.
 #[derive(Default, Clone, Copy, Debug)]
 pub struct CloserColor;
 
@@ -124,16 +722,540 @@ impl Eval for CloserColor {
                 .closer_by_color(state.player2, state.walls, &state.colors);
         player1_closer.count_ones() as i32 - player2_closer.count_ones() as i32
     }
+
+    fn cell_value(&self) -> Option<i32> {
+        Some(1)
+    }
+
+    fn symmetric(&self) -> bool {
+        true
+    }
+}
+
+/// Plays the given number of plies of `Greedy` for both sides, then scores the position with
+/// `CloserColor`, or by the final margin if the race ended the game. Unlike the static evals,
+/// this accounts for whose turn it is and for the colors each player may not pick. It is not
+/// symmetric, since `Greedy` breaks ties by color.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Race(pub u32);
+
+impl Eval for Race {
+    fn eval(&self, state: &State) -> i32 {
+        let mut state = *state;
+        for _ in 0..self.0 {
+            if state.game_over() {
+                return state.final_margin();
+            }
+            state.play(Greedy.play(&state));
+        }
+        if state.game_over() {
+            state.final_margin()
+        } else {
+            CloserColor.eval(&state)
+        }
+    }
+
+    fn cell_value(&self) -> Option<i32> {
+        Some(1)
+    }
+}
+
+/// The difference in cells each player has sealed off, which they are sure to end up with.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Sealed;
+
+impl Eval for Sealed {
+    fn eval(&self, state: &State) -> i32 {
+        let (player1, player2, _) = state.sealed();
+        player1.count_ones() as i32 - player2.count_ones() as i32
+    }
+
+    fn cell_value(&self) -> Option<i32> {
+        Some(1)
+    }
+
+    fn symmetric(&self) -> bool {
+        true
+    }
+}
+
+/// Searches on past the horizon through volatile moves, which capture at least `threshold` cells
+/// or seal off a region, for up to `cap` more plies. The player to move may always stop instead
+/// and take `eval`'s value, so only volatile lines can change the result.
+#[derive(Clone, Copy, Debug)]
+pub struct Quiescence<E> {
+    pub eval: E,
+    pub threshold: u32,
+    pub cap: u32,
+}
+
+impl<E: Eval> Quiescence<E> {
+    pub const fn new(eval: E) -> Self {
+        Self {
+            eval,
+            threshold: 8,
+            cap: 2,
+        }
+    }
+
+    /// Fail-hard alpha-beta over the volatile moves. Returns the value for the player with `sign`.
+    fn search(&self, state: &mut State, cap: u32, mut alpha: i32, beta: i32, sign: i32) -> i32 {
+        let stand_pat = sign * leaf(state, &self.eval, 0).unwrap();
+        if cap == 0 || stand_pat >= beta || state.game_over() {
+            return stand_pat;
+        }
+        alpha = max(alpha, stand_pat);
+
+        let territory = |state: &State| {
+            if sign == 1 {
+                state.player1
+            } else {
+                state.player2
+            }
+            .count_ones()
+        };
+        let sealed = |state: &State| {
+            let (player1, player2, _) = state.sealed();
+            if sign == 1 { player1 } else { player2 }.count_ones()
+        };
+        let (territory_before, sealed_before) = (territory(state), sealed(state));
+
+        let mut max_value = stand_pat;
+        let checkpoint = state.checkpoint();
+        for color in state.valid_moves() {
+            state.play(color);
+            let captured = territory(state) - territory_before;
+            let seals = sealed(state) - sealed_before > captured;
+            if captured >= self.threshold || seals {
+                let value = -self.search(state, cap - 1, -beta, -alpha, -sign);
+                max_value = max(max_value, value);
+                alpha = max(alpha, value);
+            }
+            state.restore(checkpoint);
+            if alpha >= beta {
+                break;
+            }
+        }
+        max_value
+    }
+}
+
+impl<E: Default + Eval> Default for Quiescence<E> {
+    fn default() -> Self {
+        Self::new(E::default())
+    }
+}
+
+impl<E: Eval> Eval for Quiescence<E> {
+    fn eval(&self, state: &State) -> i32 {
+        let mut state = *state;
+        let sign = if state.player1_next() { 1 } else { -1 };
+        sign * self.search(&mut state, self.cap, -INFINITY, INFINITY, sign)
+    }
+
+    fn cell_value(&self) -> Option<i32> {
+        self.eval.cell_value()
+    }
+
+    fn symmetric(&self) -> bool {
+        self.eval.symmetric()
+    }
 }
 
 impl<A: Eval, B: Eval> Eval for (A, B) {
     fn eval(&self, state: &State) -> i32 {
         256 * self.0.eval(state) + self.1.eval(state)
     }
+
+    fn symmetric(&self) -> bool {
+        self.0.symmetric() && self.1.symmetric()
+    }
 }
 
 impl<A: Eval, B: Eval, C: Eval> Eval for (A, B, C) {
     fn eval(&self, state: &State) -> i32 {
         65536 * self.0.eval(state) + 256 * self.1.eval(state) + self.2.eval(state)
     }
+
+    fn symmetric(&self) -> bool {
+        self.0.symmetric() && self.1.symmetric() && self.2.symmetric()
+    }
+}
+
+/// 1 if player 1 is to move, -1 otherwise.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Tempo;
+
+impl Eval for Tempo {
+    fn eval(&self, state: &State) -> i32 {
+        if state.player1_next() { 1 } else { -1 }
+    }
+
+    fn symmetric(&self) -> bool {
+        true
+    }
+}
+
+/// The names of the `features` weighted by `Weighted`.
+pub const FEATURES: [&str; 5] = ["captured", "accessible", "closer", "closer_color", "tempo"];
+
+/// The evals combined by `Weighted`.
+pub fn features(state: &State) -> [f64; FEATURES.len()] {
+    [
+        Captured.eval(state),
+        Accessible.eval(state),
+        Closer.eval(state),
+        CloserColor.eval(state),
+        Tempo.eval(state),
+    ]
+    .map(f64::from)
+}
+
+/// A linear combination of `features`, predicting the final margin in cells.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weighted(pub [f64; FEATURES.len()]);
+
+impl Weighted {
+    /// Evals are in 1/256ths of a cell, so that small weights still tell positions apart.
+    pub const SCALE: f64 = 256.0;
+    /// Where `tune` saves its weights, and where the tournament looks for them.
+    pub const PATH: &str = "weights.txt";
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Like `load`, but the default weights if there is no file at `path` yet.
+    pub fn load_or_default(path: impl AsRef<Path>) -> io::Result<Self> {
+        match Self::load(path) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            result => result,
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn predict(&self, features: &[f64; FEATURES.len()]) -> f64 {
+        self.0.iter().zip(features).map(|(w, x)| w * x).sum()
+    }
+}
+
+impl Default for Weighted {
+    fn default() -> Self {
+        Self([0.0, 0.25, 0.0, 0.75, 0.0])
+    }
+}
+
+impl Eval for Weighted {
+    fn eval(&self, state: &State) -> i32 {
+        (Self::SCALE * self.predict(&features(state))).round() as i32
+    }
+
+    fn cell_value(&self) -> Option<i32> {
+        Some(Self::SCALE as i32)
+    }
+
+    fn symmetric(&self) -> bool {
+        true
+    }
+}
+
+impl fmt::Display for Weighted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, weight) in FEATURES.iter().zip(self.0) {
+            writeln!(f, "{name} {weight}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Weighted {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut weights = [0.0; FEATURES.len()];
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| invalid(format!("malformed line `{line}`")))?;
+            let index = FEATURES
+                .iter()
+                .position(|feature| *feature == name)
+                .ok_or_else(|| invalid(format!("unknown feature `{name}`")))?;
+            weights[index] = value
+                .trim()
+                .parse()
+                .map_err(|_| invalid(format!("invalid weight `{value}`")))?;
+        }
+        Ok(Self(weights))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::positions;
+
+    /// The bench positions and a few from later in the same games, none of them decided yet.
+    fn states() -> Vec<State> {
+        let mut states = positions();
+        for mut state in positions() {
+            while state.round < 40 && !state.game_over() {
+                state.play(Greedy.play(&state));
+            }
+            states.push(state);
+        }
+        states.retain(|state| leaf(state, &Sealed, 1).is_none());
+        states
+    }
+
+    fn sign(state: &State) -> i32 {
+        if state.player1_next() { 1 } else { -1 }
+    }
+
+    #[test]
+    fn pvs_matches_negamax() {
+        for mut state in states() {
+            for depth in 0..4 {
+                let sign = sign(&state);
+                assert_eq!(
+                    pvs(&mut state, &CloserColor, depth, -i32::MAX, i32::MAX, sign),
+                    negamax(&mut state, &CloserColor, depth, -i32::MAX, i32::MAX, sign)
+                );
+                assert_eq!(
+                    pvs(
+                        &mut state,
+                        &(Closer, Captured),
+                        depth,
+                        -i32::MAX,
+                        i32::MAX,
+                        sign
+                    ),
+                    negamax(
+                        &mut state,
+                        &(Closer, Captured),
+                        depth,
+                        -i32::MAX,
+                        i32::MAX,
+                        sign
+                    )
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn deepen_matches_negamax() {
+        for mut state in states() {
+            let sign = sign(&state);
+            for depth in 0..5 {
+                let (_, value) = deepen(&mut state, &CloserColor, depth, None);
+                let expected = negamax(
+                    &mut state,
+                    &CloserColor,
+                    depth + 1,
+                    -i32::MAX,
+                    i32::MAX,
+                    sign,
+                );
+                assert_eq!(value, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn decided_winner_stands() {
+        let mut checked = 0;
+        for seed in 0..100 {
+            let mut state = State::generate(seed);
+            let mut decided = Vec::new();
+            while !state.game_over() {
+                decided.extend(leaf(&state, &Sealed, 1));
+                state.play(Greedy.play(&state));
+            }
+            let margin = state.final_margin();
+            for value in decided {
+                assert_eq!(value.signum(), margin.signum());
+                assert!(value.abs() > INFINITY);
+                checked += 1;
+            }
+        }
+        assert!(checked > 0);
+    }
+
+    #[test]
+    fn weighted_text() {
+        let weights = Weighted([0.5, -1.25, 0.0, 1e-3, 3.0]);
+        let text = weights.to_string();
+        assert_eq!(
+            text,
+            "captured 0.5\naccessible -1.25\ncloser 0\ncloser_color 0.001\ntempo 3\n"
+        );
+        assert_eq!(text.parse::<Weighted>().unwrap(), weights);
+
+        // Comments, blank lines and missing features, which weigh nothing.
+        let parsed: Weighted = "# tuned\n\n  closer 2\ntempo\t-1\n".parse().unwrap();
+        assert_eq!(parsed, Weighted([0.0, 0.0, 2.0, 0.0, -1.0]));
+
+        for text in ["captured", "captured x", "unknown 1", "closer 1 2"] {
+            let error = text.parse::<Weighted>().unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{text}");
+        }
+
+        let path = std::env::temp_dir().join(format!("flood-it-{}-weights", std::process::id()));
+        assert_eq!(
+            Weighted::load_or_default(&path).unwrap(),
+            Weighted::default()
+        );
+        fs::write(&path, "closer one").unwrap();
+        let error = Weighted::load_or_default(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn race() {
+        for state in states() {
+            assert_eq!(Race(0).eval(&state), CloserColor.eval(&state));
+
+            // The greedy playout, checking every move.
+            let mut playout = vec![state];
+            let mut last = state;
+            while !last.game_over() {
+                let color = Greedy.play(&last);
+                last.try_play(color).unwrap();
+                playout.push(last);
+            }
+            let margin = last.final_margin();
+            assert_eq!(Race(ROUND_LIMIT).eval(&state), margin);
+            for (plies, played) in playout.iter().enumerate() {
+                let expected = if played.game_over() {
+                    margin
+                } else {
+                    CloserColor.eval(played)
+                };
+                assert_eq!(Race(plies as u32).eval(&state), expected);
+            }
+        }
+    }
+
+    /// Whether some move of the player to move seals off more cells than it captures.
+    fn seals(state: &State) -> bool {
+        let player1_next = state.player1_next();
+        let mover = |state: &State| {
+            let (player1, player2, _) = state.sealed();
+            if player1_next {
+                (state.player1.count_ones(), player1.count_ones())
+            } else {
+                (state.player2.count_ones(), player2.count_ones())
+            }
+        };
+        let (territory, sealed) = mover(state);
+        state.valid_moves().any(|color| {
+            let mut next = *state;
+            next.play(color);
+            let (next_territory, next_sealed) = mover(&next);
+            next_sealed - sealed > next_territory - territory
+        })
+    }
+
+    #[test]
+    fn quiescence() {
+        let mut quiet = 0;
+        for state in states() {
+            let sign = sign(&state);
+            let stand_pat = leaf(&state, &CloserColor, 0).unwrap();
+
+            let capped = Quiescence {
+                cap: 0,
+                ..Quiescence::new(CloserColor)
+            };
+            assert_eq!(capped.eval(&state), stand_pat);
+            assert!(sign * Quiescence::new(CloserColor).eval(&state) >= sign * stand_pat);
+            assert_eq!(
+                Quiescence::new(CloserColor).cell_value(),
+                CloserColor.cell_value()
+            );
+
+            let sealing_only = Quiescence {
+                threshold: u32::MAX,
+                ..Quiescence::new(CloserColor)
+            };
+            if !seals(&state) {
+                assert_eq!(sealing_only.eval(&state), stand_pat);
+                quiet += 1;
+            }
+        }
+        assert!(quiet > 0);
+    }
+
+    /// Player 1's territory, which the player to move does not see the same way in a twin
+    /// position with the players swapped.
+    struct Lopsided;
+
+    impl Eval for Lopsided {
+        fn eval(&self, state: &State) -> i32 {
+            state.player1.count_ones() as i32
+        }
+    }
+
+    #[test]
+    fn symmetric_evals() {
+        let mut labels = Color::LIST;
+        labels.rotate_right(3);
+        let evals: [&dyn Eval; 9] = [
+            &Captured,
+            &Accessible,
+            &Closer,
+            &CloserColor,
+            &Sealed,
+            &Tempo,
+            &Weighted::default(),
+            &Quiescence::new(CloserColor),
+            &(CloserColor, Accessible, Captured),
+        ];
+        for state in states().iter().filter(|state| state.round >= 2) {
+            let twin = crate::game::tests::twin(state, labels);
+            for eval in evals {
+                assert!(eval.symmetric());
+                assert_eq!(
+                    sign(&twin) * eval.eval(&twin),
+                    sign(state) * eval.eval(state)
+                );
+            }
+        }
+        assert!(!Race(4).symmetric() && !Lopsided.symmetric());
+        assert!(!Quiescence::new(Lopsided).symmetric() && !(Lopsided, Captured).symmetric());
+    }
+
+    /// Searches `state` and then its twin with one table, and compares both with `negamax`.
+    fn shared_table_matches_negamax<E: Eval>(state: &State, eval: &E) {
+        let mut labels = Color::LIST;
+        labels.rotate_left(2);
+        let mut search = Search {
+            deadline: Deadline::new(None),
+            table: Table::new(Some(TABLE_BITS), eval.symmetric()),
+        };
+        for mut state in [*state, crate::game::tests::twin(state, labels)] {
+            let sign = sign(&state);
+            let value = pvs_until(&mut state, eval, 4, -i32::MAX, i32::MAX, sign, &mut search);
+            let expected = negamax(&mut state, eval, 4, -i32::MAX, i32::MAX, sign);
+            assert_eq!(value, Some(expected));
+        }
+    }
+
+    #[test]
+    fn table_shares_only_symmetric_values() {
+        for state in states().iter().filter(|state| state.round >= 2) {
+            shared_table_matches_negamax(state, &CloserColor);
+            shared_table_matches_negamax(state, &Lopsided);
+        }
+    }
 }
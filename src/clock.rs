@@ -0,0 +1,193 @@
+use core::fmt;
+use core::str::FromStr;
+use std::time::Duration;
+
+use crate::game::{ROUND_LIMIT, State};
+
+/// How much time players get for their moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeControl {
+    /// Each player starts with `base` and gains `increment` after each of their moves.
+    Increment { base: Duration, increment: Duration },
+    /// Each move must be made within the given time.
+    MoveTime(Duration),
+}
+
+impl TimeControl {
+    pub fn initial(self) -> Duration {
+        match self {
+            TimeControl::Increment { base, .. } => base,
+            TimeControl::MoveTime(movetime) => movetime,
+        }
+    }
+
+    pub fn increment(self) -> Duration {
+        match self {
+            TimeControl::Increment { increment, .. } => increment,
+            TimeControl::MoveTime(_) => Duration::ZERO,
+        }
+    }
+
+    /// The time left after a move that took `elapsed`, or `None` if the player flagged.
+    pub fn after_move(self, remaining: Duration, elapsed: Duration) -> Option<Duration> {
+        let left = remaining.checked_sub(elapsed)?;
+        Some(match self {
+            TimeControl::Increment { increment, .. } => left + increment,
+            TimeControl::MoveTime(movetime) => movetime,
+        })
+    }
+}
+
+/// Parses `<base>+<increment>` or `<time>/move`, in seconds, e.g. `60+0.5` or `1/move`.
+impl FromStr for TimeControl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let seconds = |s: &str| {
+            s.parse()
+                .ok()
+                .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                .ok_or_else(|| format!("invalid time `{s}`"))
+        };
+
+        if let Some(movetime) = s.strip_suffix("/move") {
+            Ok(TimeControl::MoveTime(seconds(movetime)?))
+        } else if let Some((base, increment)) = s.split_once('+') {
+            Ok(TimeControl::Increment {
+                base: seconds(base)?,
+                increment: seconds(increment)?,
+            })
+        } else {
+            Err(format!("invalid time control `{s}`"))
+        }
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeControl::Increment { base, increment } => {
+                write!(f, "{}+{}", base.as_secs_f64(), increment.as_secs_f64())
+            }
+            TimeControl::MoveTime(movetime) => write!(f, "{}/move", movetime.as_secs_f64()),
+        }
+    }
+}
+
+/// The clocks as seen by the player to move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Clock {
+    /// The time the player can use before flagging.
+    pub remaining: Duration,
+    /// The time added to the player's clock after this move.
+    pub increment: Duration,
+    pub opponent_remaining: Duration,
+    pub opponent_increment: Duration,
+    /// Whether `remaining` is reset after every move rather than carried over.
+    pub per_move: bool,
+}
+
+impl Clock {
+    /// The largest number of moves the player is assumed to still have to make.
+    const HORIZON: u32 = 25;
+
+    /// A reasonable amount of time to spend on the next move of `state`.
+    pub fn budget(&self, state: &State) -> Duration {
+        if self.per_move {
+            return self.remaining * 3 / 4;
+        }
+        let moves_left = (ROUND_LIMIT.saturating_sub(state.round) / 2).clamp(1, Self::HORIZON);
+        (self.remaining / moves_left + self.increment * 3 / 4).min(self.remaining / 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn parse_and_display() {
+        let cases = [
+            (
+                "60+0.5",
+                TimeControl::Increment {
+                    base: ms(60_000),
+                    increment: ms(500),
+                },
+            ),
+            (
+                "0.25+0",
+                TimeControl::Increment {
+                    base: ms(250),
+                    increment: Duration::ZERO,
+                },
+            ),
+            ("1/move", TimeControl::MoveTime(ms(1000))),
+            ("0.05/move", TimeControl::MoveTime(ms(50))),
+        ];
+        for (text, time_control) in cases {
+            assert_eq!(text.parse(), Ok(time_control));
+            assert_eq!(time_control.to_string(), text);
+        }
+        for text in [
+            "", "60", "60+", "+1", "a+1", "-1+0", "1+-1", "x/move", "-1/move", "1/moves",
+        ] {
+            assert!(text.parse::<TimeControl>().is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn after_move() {
+        let increment = TimeControl::Increment {
+            base: ms(1000),
+            increment: ms(100),
+        };
+        assert_eq!(increment.initial(), ms(1000));
+        assert_eq!(increment.increment(), ms(100));
+        assert_eq!(increment.after_move(ms(1000), ms(300)), Some(ms(800)));
+        assert_eq!(increment.after_move(ms(50), ms(50)), Some(ms(100)));
+        assert_eq!(increment.after_move(ms(50), ms(51)), None);
+
+        let movetime = TimeControl::MoveTime(ms(200));
+        assert_eq!(movetime.initial(), ms(200));
+        assert_eq!(movetime.increment(), Duration::ZERO);
+        assert_eq!(movetime.after_move(ms(200), ms(150)), Some(ms(200)));
+        assert_eq!(movetime.after_move(ms(200), ms(200)), Some(ms(200)));
+        assert_eq!(movetime.after_move(ms(200), ms(201)), None);
+    }
+
+    #[test]
+    fn budget() {
+        let clock = |remaining, increment, per_move| Clock {
+            remaining,
+            increment,
+            opponent_remaining: ms(1000),
+            opponent_increment: increment,
+            per_move,
+        };
+        let mut state = State::generate(0);
+
+        assert_eq!(clock(ms(400), ms(0), true).budget(&state), ms(300));
+        // Early on, the time is spread over the horizon.
+        assert_eq!(clock(ms(2500), ms(0), false).budget(&state), ms(100));
+        assert_eq!(clock(ms(2500), ms(40), false).budget(&state), ms(130));
+
+        // Near the round limit, fewer moves are left, but no move gets more than half the time.
+        state.round = ROUND_LIMIT - 10;
+        assert_eq!(clock(ms(2500), ms(0), false).budget(&state), ms(500));
+        state.round = ROUND_LIMIT - 1;
+        assert_eq!(clock(ms(2500), ms(0), false).budget(&state), ms(1250));
+
+        for round in 0..ROUND_LIMIT {
+            state.round = round;
+            for remaining in [ms(0), ms(1), ms(10), ms(5000)] {
+                let clock = clock(remaining, ms(1000), false);
+                assert!(clock.budget(&state) <= remaining);
+            }
+        }
+    }
+}
@@ -0,0 +1,320 @@
+//! Self-play training data.
+//!
+//! `datagen` plays `games` games between two contestants and writes every position to `shards`
+//! files named `shard-<i>.bin`. Game `g` is played on seed `seed + g` and written to shard
+//! `g % shards`, in order, so the output only depends on the arguments.
+//!
+//! Each shard starts with the magic bytes `FLDT` and a little-endian `u32` format version,
+//! followed by fixed size records until the end of the file. All integers are little-endian and
+//! every mask is 16 `u16` rows, bit `c` of row `r` being the cell `(r, c)`. Colors are stored as
+//! their index in `Color::LIST`.
+//!
+//! | offset | size | field                                                          |
+//! |--------|------|----------------------------------------------------------------|
+//! | 0      | 256  | `colors`, 8 masks                                              |
+//! | 256    | 32   | `walls`                                                        |
+//! | 288    | 32   | `player1`                                                      |
+//! | 320    | 32   | `player2`                                                      |
+//! | 352    | 8    | seed, `u64`                                                    |
+//! | 360    | 1    | round, `u8`                                                    |
+//! | 361    | 1    | player to move, 1 or 2                                         |
+//! | 362    | 1    | player 1's last move, `0xff` if none                           |
+//! | 363    | 1    | player 2's last move, `0xff` if none                           |
+//! | 364    | 1    | chosen move                                                    |
+//! | 365    | 4    | search score for the player to move, `i32`, `i32::MIN` if none |
+//! | 369    | 2    | final margin for player 1, `i16`                               |
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::thread;
+
+use crate::arena::{Contestant, play_game};
+use crate::bot::{Player, Record, Recorder};
+use crate::game::{Color, State};
+use crate::mask::Mask;
+
+pub const MAGIC: [u8; 4] = *b"FLDT";
+pub const VERSION: u32 = 1;
+pub const RECORD_SIZE: usize = 371;
+
+const NONE: u8 = 0xff;
+
+/// A recorded position labelled with the final margin of its game.
+#[derive(Clone, Copy)]
+pub struct Sample {
+    pub record: Record,
+    pub margin: i32,
+}
+
+impl Sample {
+    pub fn encode(&self) -> [u8; RECORD_SIZE] {
+        let state = &self.record.state;
+        let mut bytes = Vec::with_capacity(RECORD_SIZE);
+
+        for mask in state
+            .colors
+            .iter()
+            .chain([&state.walls, &state.player1, &state.player2])
+        {
+            for row in <[u16; 16]>::from(*mask) {
+                bytes.extend(row.to_le_bytes());
+            }
+        }
+        bytes.extend(state.seed.to_le_bytes());
+        bytes.push(state.round as u8);
+        bytes.push(if state.player1_next() { 1 } else { 2 });
+        bytes.push(state.player1_last_move.map_or(NONE, |color| color as u8));
+        bytes.push(state.player2_last_move.map_or(NONE, |color| color as u8));
+        bytes.push(self.record.color as u8);
+        bytes.extend(self.record.score.unwrap_or(i32::MIN).to_le_bytes());
+        bytes.extend((self.margin as i16).to_le_bytes());
+
+        bytes.try_into().unwrap()
+    }
+
+    pub fn decode(bytes: &[u8; RECORD_SIZE]) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        let color = |byte: u8| {
+            Color::LIST
+                .get(usize::from(byte))
+                .copied()
+                .ok_or_else(|| invalid("invalid color"))
+        };
+        let last_move = |byte: u8| {
+            if byte == NONE {
+                Ok(None)
+            } else {
+                color(byte).map(Some)
+            }
+        };
+
+        let mut masks = [Mask::empty(); 11];
+        for (mask, chunk) in masks.iter_mut().zip(bytes.chunks_exact(32)) {
+            let mut rows = [0u16; 16];
+            for (row, pair) in rows.iter_mut().zip(chunk.chunks_exact(2)) {
+                *row = u16::from_le_bytes([pair[0], pair[1]]);
+            }
+            *mask = rows.into();
+        }
+
+        let state = State {
+            colors: masks[..8].try_into().unwrap(),
+            walls: masks[8],
+            player1: masks[9],
+            player2: masks[10],
+            player1_last_move: last_move(bytes[362])?,
+            player2_last_move: last_move(bytes[363])?,
+            round: u32::from(bytes[360]),
+            seed: u64::from_le_bytes(bytes[352..360].try_into().unwrap()),
+        };
+        if !state.is_valid() || (bytes[361] == 1) != state.player1_next() {
+            return Err(invalid("invalid state"));
+        }
+
+        let score = i32::from_le_bytes(bytes[365..369].try_into().unwrap());
+        Ok(Self {
+            record: Record {
+                state,
+                color: color(bytes[364])?,
+                score: (score != i32::MIN).then_some(score),
+            },
+            margin: i32::from(i16::from_le_bytes([bytes[369], bytes[370]])),
+        })
+    }
+}
+
+/// Plays a game on `seed` and returns every position with its label.
+pub fn self_play(seed: u64, player1: impl Player, player2: impl Player) -> Vec<Sample> {
+    let mut player1_records = Vec::new();
+    let mut player2_records = Vec::new();
+    let result = play_game(
+        seed,
+        &mut Recorder {
+            inner: player1,
+            records: &mut player1_records,
+        },
+        &mut Recorder {
+            inner: player2,
+            records: &mut player2_records,
+        },
+    );
+
+    let mut records: Vec<_> = player1_records.into_iter().chain(player2_records).collect();
+    records.sort_by_key(|record| record.state.round);
+    records
+        .into_iter()
+        .map(|record| Sample {
+            record,
+            margin: result.margin,
+        })
+        .collect()
+}
+
+/// Runs `job` on `0..jobs`, spread over the available threads, and returns the results in order.
+pub fn parallel<T: Send>(jobs: u64, job: impl Fn(u64) -> T + Sync) -> Vec<T> {
+    let job = &job;
+    let threads =
+        (thread::available_parallelism().map_or(1, usize::from) as u64).clamp(1, jobs.max(1));
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|thread| {
+                scope.spawn(move || {
+                    (thread..jobs)
+                        .step_by(threads as usize)
+                        .map(job)
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut results: Vec<_> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap().into_iter())
+            .collect();
+        (0..jobs)
+            .map(|job| results[(job % threads) as usize].next().unwrap())
+            .collect()
+    })
+}
+
+pub fn write_shard(
+    path: impl AsRef<Path>,
+    samples: impl IntoIterator<Item = Sample>,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    for sample in samples {
+        writer.write_all(&sample.encode())?;
+    }
+    writer.flush()
+}
+
+pub fn read_shard(path: impl AsRef<Path>) -> io::Result<Vec<Sample>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut header = [0; 8];
+    reader.read_exact(&mut header)?;
+    if header[..4] != MAGIC || header[4..] != VERSION.to_le_bytes() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a version 1 data shard",
+        ));
+    }
+
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let records = bytes.chunks_exact(RECORD_SIZE);
+    if !records.remainder().is_empty() {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    records
+        .map(|chunk| Sample::decode(chunk.try_into().unwrap()))
+        .collect()
+}
+
+/// Plays games `0..games` and writes them to `shards` files in `dir`, as described above.
+pub fn generate(
+    dir: &Path,
+    games: u64,
+    shards: u64,
+    seed: u64,
+    player1: &Contestant,
+    player2: &Contestant,
+) -> io::Result<()> {
+    if shards == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "at least one shard is needed",
+        ));
+    }
+    fs::create_dir_all(dir)?;
+
+    parallel(shards, |shard| {
+        let samples = (shard..games)
+            .step_by(shards as usize)
+            .flat_map(|game| self_play(seed + game, (player1.make)(), (player2.make)()));
+        write_shard(dir.join(format!("shard-{shard}.bin")), samples)
+    })
+    .into_iter()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::find_contestant;
+    use crate::bot::Greedy;
+
+    /// Every position of a few games, some of them with search scores.
+    fn samples() -> Vec<Sample> {
+        let mut samples: Vec<_> = (0..4)
+            .flat_map(|seed| self_play(seed, Greedy, Greedy))
+            .collect();
+        for (i, sample) in samples.iter_mut().enumerate() {
+            sample.record.score = (i % 3 != 0).then_some(i as i32 - 100);
+        }
+        samples
+    }
+
+    fn assert_same(a: &Sample, b: &Sample) {
+        assert_eq!(a.record.state, b.record.state);
+        assert_eq!(a.record.color, b.record.color);
+        assert_eq!(a.record.score, b.record.score);
+        assert_eq!(a.margin, b.margin);
+    }
+
+    /// A path in the temporary directory that no other test uses.
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("flood-it-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn encode_decode() {
+        for sample in samples() {
+            let bytes = sample.encode();
+            assert_same(&Sample::decode(&bytes).unwrap(), &sample);
+
+            let mut bytes = bytes;
+            bytes[364] = 8;
+            assert!(Sample::decode(&bytes).is_err());
+        }
+    }
+
+    #[test]
+    fn write_read_shard() {
+        let path = temp_path("shard.bin");
+        let samples = samples();
+        write_shard(&path, samples.iter().copied()).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(bytes.len(), 8 + samples.len() * RECORD_SIZE);
+        let read = read_shard(&path).unwrap();
+        assert_eq!(read.len(), samples.len());
+        for (a, b) in read.iter().zip(&samples) {
+            assert_same(a, b);
+        }
+
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(read_shard(&path).is_err());
+        let mut bytes = bytes;
+        bytes[0] = b'X';
+        fs::write(&path, &bytes).unwrap();
+        assert!(read_shard(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn no_shards() {
+        let greedy = find_contestant("Greedy").unwrap();
+        let dir = temp_path("no-shards");
+        assert!(generate(&dir, 10, 0, 0, greedy, greedy).is_err());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn parallel_keeps_order() {
+        for jobs in [0, 1, 7, 100] {
+            let expected: Vec<_> = (0..jobs).map(|job| job * job).collect();
+            assert_eq!(parallel(jobs, |job| job * job), expected);
+        }
+    }
+}
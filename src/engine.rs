@@ -0,0 +1,538 @@
+//! A line based protocol for engines running in another process.
+//!
+//! The controller writes commands to the engine's stdin and reads its replies from stdout, one
+//! per line. Tokens are separated by whitespace and colors are written by name (`red`, `orange`,
+//! `yellow`, `green`, `cyan`, `blue`, `purple` or `pink`).
+//!
+//! - `flood`: sent once at startup. The engine replies `id name <name>`, then `floodok`.
+//! - `isready`: the engine replies `readyok` once it is ready for more commands.
+//! - `newgame`: the following positions belong to a new game.
+//! - `position seed <seed> [moves <color>...]`: the board generated from `seed` after the given
+//!   moves, starting with player 1's.
+//! - `position seed <seed> state <round> <last1> <last2> <player1> <player2>`: the board generated
+//!   from `seed` after `round` moves, with the players' last moves (`-` if none) and territories
+//!   (64 hex digits, see `Mask::from_hex`). Used when the moves are not known.
+//! - `go [movetime <ms>] [p1time <ms>] [p2time <ms>] [p1inc <ms>] [p2inc <ms>]`: the engine
+//!   replies `bestmove <color>` for the player to move in the last position.
+//! - `quit`: the engine exits.
+//!
+//! Engines reply `error <message>` to commands they cannot process and ignore unknown commands.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::bot::Player;
+use crate::clock::Clock;
+use crate::game::{Color, State};
+use crate::mask::Mask;
+
+/// The time control sent with `go`. Fields that were not given are `None`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Go {
+    pub movetime: Option<Duration>,
+    pub p1time: Option<Duration>,
+    pub p2time: Option<Duration>,
+    pub p1inc: Option<Duration>,
+    pub p2inc: Option<Duration>,
+}
+
+impl Go {
+    pub fn parse<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<Self, String> {
+        let mut go = Self::default();
+        while let Some(name) = tokens.next() {
+            let field = match name {
+                "movetime" => &mut go.movetime,
+                "p1time" => &mut go.p1time,
+                "p2time" => &mut go.p2time,
+                "p1inc" => &mut go.p1inc,
+                "p2inc" => &mut go.p2inc,
+                _ => return Err(format!("unknown go parameter `{name}`")),
+            };
+            let value = tokens
+                .next()
+                .ok_or_else(|| format!("missing value for `{name}`"))?;
+            let millis = value
+                .parse()
+                .map_err(|_| format!("invalid value `{value}` for `{name}`"))?;
+            *field = Some(Duration::from_millis(millis));
+        }
+        Ok(go)
+    }
+}
+
+impl Go {
+    pub fn from_clock(clock: &Clock, player1_next: bool) -> Self {
+        if clock.per_move {
+            return Self {
+                movetime: Some(clock.remaining),
+                ..Self::default()
+            };
+        }
+
+        let mut times = [
+            (clock.remaining, clock.increment),
+            (clock.opponent_remaining, clock.opponent_increment),
+        ];
+        if !player1_next {
+            times.reverse();
+        }
+        let [(p1time, p1inc), (p2time, p2inc)] = times;
+        Self {
+            movetime: None,
+            p1time: Some(p1time),
+            p2time: Some(p2time),
+            p1inc: Some(p1inc),
+            p2inc: Some(p2inc),
+        }
+    }
+
+    /// The clock of the player to move, if a time control was given.
+    pub fn clock(&self, player1_next: bool) -> Option<Clock> {
+        if let Some(movetime) = self.movetime {
+            return Some(Clock {
+                remaining: movetime,
+                increment: Duration::ZERO,
+                opponent_remaining: movetime,
+                opponent_increment: Duration::ZERO,
+                per_move: true,
+            });
+        }
+
+        let mut times = [
+            (self.p1time?, self.p1inc.unwrap_or_default()),
+            (self.p2time?, self.p2inc.unwrap_or_default()),
+        ];
+        if !player1_next {
+            times.reverse();
+        }
+        let [
+            (remaining, increment),
+            (opponent_remaining, opponent_increment),
+        ] = times;
+        Some(Clock {
+            remaining,
+            increment,
+            opponent_remaining,
+            opponent_increment,
+            per_move: false,
+        })
+    }
+}
+
+impl std::fmt::Display for Go {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("go")?;
+        for (name, value) in [
+            ("movetime", self.movetime),
+            ("p1time", self.p1time),
+            ("p2time", self.p2time),
+            ("p1inc", self.p1inc),
+            ("p2inc", self.p2inc),
+        ] {
+            if let Some(value) = value {
+                write!(f, " {name} {}", value.as_millis())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_last_move(token: &str) -> Result<Option<Color>, String> {
+    if token == "-" {
+        Ok(None)
+    } else {
+        token.parse().map(Some)
+    }
+}
+
+/// Parses the arguments of a `position` command.
+pub fn parse_position<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<State, String> {
+    if tokens.next() != Some("seed") {
+        return Err("expected `seed`".to_string());
+    }
+    let seed = tokens
+        .next()
+        .and_then(|token| token.parse().ok())
+        .ok_or("invalid seed")?;
+    let mut state = State::generate(seed);
+
+    match tokens.next() {
+        None => {}
+        Some("moves") => {
+            for token in tokens {
+                let color = token.parse()?;
+                state
+                    .try_play(color)
+                    .map_err(|error| format!("illegal move `{color}`: {error}"))?;
+            }
+        }
+        Some("state") => {
+            let mut next = || tokens.next().ok_or("incomplete state");
+            state.round = next()?.parse().map_err(|_| "invalid round")?;
+            state.player1_last_move = parse_last_move(next()?)?;
+            state.player2_last_move = parse_last_move(next()?)?;
+            state.player1 = Mask::from_hex(next()?).ok_or("invalid territory")?;
+            state.player2 = Mask::from_hex(next()?).ok_or("invalid territory")?;
+            state
+                .validate()
+                .map_err(|error| format!("invalid state: {error}"))?;
+        }
+        Some(token) => return Err(format!("unexpected `{token}`")),
+    }
+    Ok(state)
+}
+
+/// Answers protocol commands from `input` with players built by `make`, until `quit` or the end
+/// of the input.
+pub fn serve(
+    name: &str,
+    make: impl Fn() -> Box<dyn Player>,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> io::Result<()> {
+    let mut player = make();
+    let mut state = None;
+
+    for line in input.lines() {
+        let line = line?;
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("flood") => {
+                writeln!(output, "id name {name}")?;
+                writeln!(output, "floodok")?;
+            }
+            Some("isready") => writeln!(output, "readyok")?,
+            Some("newgame") => {
+                player = make();
+                state = None;
+            }
+            Some("position") => match parse_position(tokens) {
+                Ok(position) => state = Some(position),
+                Err(message) => {
+                    state = None;
+                    writeln!(output, "error {message}")?;
+                }
+            },
+            Some("go") => match (Go::parse(tokens), &state) {
+                (Err(message), _) => writeln!(output, "error {message}")?,
+                (Ok(_), None) => writeln!(output, "error no position")?,
+                (Ok(_), Some(state)) if state.game_over() => writeln!(output, "error game over")?,
+                (Ok(go), Some(state)) => {
+                    let color = match go.clock(state.player1_next()) {
+                        Some(clock) => player.play_timed(state, &clock),
+                        None => player.play(state),
+                    };
+                    writeln!(output, "bestmove {color}")?;
+                }
+            },
+            Some("quit") => break,
+            _ => {}
+        }
+        output.flush()?;
+    }
+    Ok(())
+}
+
+/// A player in another process, spoken to with the protocol above. An engine that does not
+/// answer in time is killed.
+pub struct ExternalPlayer {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    /// The lines of the engine's stdout, read on another thread so that waiting can time out.
+    lines: Receiver<io::Result<String>>,
+    seed: u64,
+    round: Option<u32>,
+    /// The moves of the current game, if every one of them is known.
+    moves: Option<Vec<Color>>,
+}
+
+impl ExternalPlayer {
+    /// How long the engine has to answer `flood`.
+    const STARTUP_TIME: Duration = Duration::from_secs(10);
+    /// How long the engine has to exit after `quit`.
+    const QUIT_TIME: Duration = Duration::from_secs(1);
+
+    pub fn spawn(program: &str, args: &[&str]) -> io::Result<Self> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in stdout.lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut player = Self {
+            name: program.to_string(),
+            child,
+            stdin,
+            lines,
+            seed: 0,
+            round: None,
+            moves: None,
+        };
+        player.send("flood")?;
+        let deadline = Instant::now() + Self::STARTUP_TIME;
+        loop {
+            let line = player.receive(Some(deadline))?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                player.name = name.to_string();
+            } else if line == "floodok" {
+                return Ok(player);
+            }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{line}")?;
+        self.stdin.flush()
+    }
+
+    /// The next line from the engine. If it does not come by `deadline`, the engine is killed.
+    fn receive(&mut self, deadline: Option<Instant>) -> io::Result<String> {
+        let line = match deadline {
+            Some(deadline) => {
+                match self
+                    .lines
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                {
+                    Ok(line) => line,
+                    Err(RecvTimeoutError::Timeout) => {
+                        let _ = self.child.kill();
+                        return Err(io::ErrorKind::TimedOut.into());
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                }
+            }
+            None => self
+                .lines
+                .recv()
+                .map_err(|_| io::Error::from(io::ErrorKind::UnexpectedEof))?,
+        };
+        Ok(line?.trim().to_string())
+    }
+
+    /// Extends the known moves to `state`, returning whether this is still the same game.
+    fn follow(&mut self, state: &State) -> bool {
+        let same_game = self.seed == state.seed && self.round.is_some_and(|r| r < state.round);
+        let opponent_last_move = if state.player1_next() {
+            state.player2_last_move
+        } else {
+            state.player1_last_move
+        };
+
+        if same_game {
+            if let Some(moves) = &mut self.moves {
+                if moves.len() + 1 == state.round as usize {
+                    moves.push(opponent_last_move.unwrap());
+                } else {
+                    self.moves = None;
+                }
+            }
+        } else {
+            self.moves = match state.round {
+                0 => Some(Vec::new()),
+                1 => Some(vec![opponent_last_move.unwrap()]),
+                _ => None,
+            };
+        }
+
+        self.seed = state.seed;
+        self.round = Some(state.round);
+        same_game
+    }
+
+    fn position(&self, state: &State) -> String {
+        match &self.moves {
+            Some(moves) if moves.is_empty() => format!("position seed {}", state.seed),
+            Some(moves) => {
+                let moves: Vec<_> = moves.iter().map(|color| color.name()).collect();
+                format!("position seed {} moves {}", state.seed, moves.join(" "))
+            }
+            None => {
+                let last_move = |color: Option<Color>| color.map_or("-", Color::name);
+                format!(
+                    "position seed {} state {} {} {} {:x} {:x}",
+                    state.seed,
+                    state.round,
+                    last_move(state.player1_last_move),
+                    last_move(state.player2_last_move),
+                    state.player1,
+                    state.player2,
+                )
+            }
+        }
+    }
+
+    /// Asks for a move, giving up once `remaining` has passed if there is a time limit.
+    fn request(
+        &mut self,
+        state: &State,
+        go: &Go,
+        remaining: Option<Duration>,
+    ) -> io::Result<Color> {
+        let deadline = remaining.map(|remaining| Instant::now() + remaining);
+        if !self.follow(state) {
+            self.send("newgame")?;
+        }
+        let position = self.position(state);
+        self.send(&position)?;
+        self.send(&go.to_string())?;
+
+        loop {
+            let line = self.receive(deadline)?;
+            if let Some(color) = line.strip_prefix("bestmove ") {
+                let color = color
+                    .parse()
+                    .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))?;
+                if let Some(moves) = &mut self.moves {
+                    moves.push(color);
+                }
+                return Ok(color);
+            } else if let Some(message) = line.strip_prefix("error ") {
+                return Err(io::Error::other(message.to_string()));
+            }
+        }
+    }
+}
+
+impl Player for ExternalPlayer {
+    /// The engine's move. An engine that fails can't forfeit here, so it is reported on stderr
+    /// and the first valid move is played instead. Use `try_play` to forfeit it.
+    fn play(&mut self, state: &State) -> Color {
+        self.try_play(state, None).unwrap_or_else(|error| {
+            eprintln!("engine `{}` failed: {error}", self.name);
+            state.valid_moves().next().unwrap()
+        })
+    }
+
+    fn play_timed(&mut self, state: &State, clock: &Clock) -> Color {
+        self.try_play(state, Some(clock)).unwrap_or_else(|error| {
+            eprintln!("engine `{}` failed: {error}", self.name);
+            state.valid_moves().next().unwrap()
+        })
+    }
+
+    fn try_play(&mut self, state: &State, clock: Option<&Clock>) -> io::Result<Color> {
+        let go = clock.map_or_else(Go::default, |clock| {
+            Go::from_clock(clock, state.player1_next())
+        });
+        self.request(state, &go, clock.map(|clock| clock.remaining))
+    }
+}
+
+impl Drop for ExternalPlayer {
+    fn drop(&mut self) {
+        if self.send("quit").is_ok() {
+            let deadline = Instant::now() + Self::QUIT_TIME;
+            while Instant::now() < deadline {
+                match self.child.try_wait() {
+                    Ok(None) => thread::sleep(Duration::from_millis(10)),
+                    _ => return,
+                }
+            }
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::Greedy;
+
+    fn replies(input: &str) -> Vec<String> {
+        let mut output = Vec::new();
+        serve("test", || Box::new(Greedy), input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// An engine that answers `flood` and then hangs, ignoring every command.
+    fn hanging_engine() -> ExternalPlayer {
+        ExternalPlayer::spawn("sh", &["-c", "read line; echo floodok; exec sleep 60"]).unwrap()
+    }
+
+    #[test]
+    fn hanging_engine_times_out() {
+        let mut engine = hanging_engine();
+        let state = State::generate(0);
+        let clock = Clock {
+            remaining: Duration::from_millis(100),
+            increment: Duration::ZERO,
+            opponent_remaining: Duration::from_millis(100),
+            opponent_increment: Duration::ZERO,
+            per_move: true,
+        };
+        let start = Instant::now();
+        let error = engine.try_play(&state, Some(&clock)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(engine.try_play(&state, Some(&clock)).is_err());
+
+        let start = Instant::now();
+        drop(hanging_engine());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn go_round_trip() {
+        let ms = Duration::from_millis;
+        let cases = [
+            Go::default(),
+            Go {
+                movetime: Some(ms(250)),
+                ..Go::default()
+            },
+            Go {
+                movetime: None,
+                p1time: Some(ms(60_000)),
+                p2time: Some(ms(1)),
+                p1inc: Some(ms(500)),
+                p2inc: Some(Duration::ZERO),
+            },
+        ];
+        for go in cases {
+            let text = go.to_string();
+            assert_eq!(Go::parse(text.split_whitespace().skip(1)), Ok(go), "{text}");
+        }
+        assert_eq!(
+            Go::parse("p2inc 3 movetime 7".split_whitespace())
+                .unwrap()
+                .to_string(),
+            "go movetime 7 p2inc 3"
+        );
+        for text in ["movetime", "movetime x", "movetime -1", "depth 3"] {
+            assert!(Go::parse(text.split_whitespace()).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn bad_position_clears_state() {
+        let replies = replies("position seed 5\ngo\nposition seed 5 moves red red\ngo\n");
+        assert!(replies[0].starts_with("bestmove "));
+        assert!(replies[1].starts_with("error illegal move"));
+        assert_eq!(replies[2], "error no position");
+    }
+}
@@ -1,3 +1,6 @@
+use core::fmt;
+use core::str::FromStr;
+
 use rand::prelude::*;
 use rand_pcg::Pcg64;
 use termion::color::{
@@ -7,9 +10,13 @@ use termion::color::{
 
 use crate::mask::Mask;
 
+/// The game ends after this many moves, counting both players'.
 pub const ROUND_LIMIT: u32 = 100;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// A move: the color a player floods their territory with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Color {
     Red,
     Orange,
@@ -22,6 +29,7 @@ pub enum Color {
 }
 
 impl Color {
+    /// Every color, in the order used for `State::colors` and `ValidMoves`.
     pub const LIST: [Color; 8] = [
         Color::Red,
         Color::Orange,
@@ -32,6 +40,45 @@ impl Color {
         Color::Purple,
         Color::Pink,
     ];
+
+    /// The lowercase name used by the engine protocol.
+    pub fn name(self) -> &'static str {
+        match self {
+            Color::Red => "red",
+            Color::Orange => "orange",
+            Color::Yellow => "yellow",
+            Color::Green => "green",
+            Color::Cyan => "cyan",
+            Color::Blue => "blue",
+            Color::Purple => "purple",
+            Color::Pink => "pink",
+        }
+    }
+
+    /// The letter used by `State`'s `Display`: the first letter of the name, except `k` for pink.
+    pub fn letter(self) -> char {
+        match self {
+            Color::Pink => 'k',
+            _ => self.name().chars().next().unwrap(),
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Color::LIST
+            .into_iter()
+            .find(|color| color.name() == s)
+            .ok_or_else(|| format!("unknown color `{s}`"))
+    }
 }
 
 const PROTECTED: [(usize, usize); 24] = [
@@ -82,9 +129,7 @@ fn generate(seed: u64) -> ([Mask; 8], Mask) {
     }
 
     let mut directions = [false; 28];
-    for i in 0..14 {
-        directions[i] = true;
-    }
+    directions[..14].fill(true);
 
     directions.shuffle(&mut rng);
     let mut position = Mask::one_hot(1, 1);
@@ -119,19 +164,27 @@ fn generate(seed: u64) -> ([Mask; 8], Mask) {
     (colors, !used)
 }
 
-#[derive(Clone, Copy)]
+/// A position. Cells are in exactly one of `colors` and `walls`, except the two starting corners;
+/// cells captured by a player stay in their color's mask.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct State {
     pub colors: [Mask; 8],
     pub walls: Mask,
+    /// Player 1's territory, starting in the top left corner.
     pub player1: Mask,
+    /// Player 2's territory, starting in the bottom right corner.
     pub player2: Mask,
     pub player1_last_move: Option<Color>,
     pub player2_last_move: Option<Color>,
+    /// The number of moves made so far.
     pub round: u32,
+    /// The seed the board was generated from.
     pub seed: u64,
 }
 
 impl State {
+    /// The starting position of the board generated from `seed`.
     pub fn generate(seed: u64) -> Self {
         let (colors, walls) = generate(seed);
         Self {
@@ -146,74 +199,98 @@ impl State {
         }
     }
 
+    /// Prints the board to a terminal with colored backgrounds.
     pub fn print(&self) {
-        for r in 0..16 {
-            for c in 0..16 {
-                macro_rules! test {
-                    ($mask:expr, $color:ident) => {
-                        if $mask.get(r, c) {
-                            print!("{}  ", Bg($color));
-                            continue;
-                        }
-                    };
-                }
+        print!("{}", self.colored());
+    }
 
-                test!(self.walls, Black);
-                test!(self.player1, White);
-                test!(self.player2, LightBlack);
-                test!(self.colors[0], Red);
-                test!(self.colors[1], LightRed);
-                test!(self.colors[2], Yellow);
-                test!(self.colors[3], Green);
-                test!(self.colors[4], Cyan);
-                test!(self.colors[5], Blue);
-                test!(self.colors[6], Magenta);
-                test!(self.colors[7], LightMagenta);
-
-                panic!();
-            }
-            println!("{}", Bg(Reset));
+    /// Displays the board with ANSI colored backgrounds instead of letters.
+    pub fn colored(&self) -> Colored<'_> {
+        Colored(self)
+    }
+
+    /// What `Display` writes for a cell.
+    fn symbol(&self, row: usize, col: usize) -> char {
+        if self.walls.get(row, col) {
+            'X'
+        } else if self.player1.get(row, col) {
+            '1'
+        } else if self.player2.get(row, col) {
+            '2'
+        } else {
+            Color::LIST
+                .into_iter()
+                .find(|&color| self.colors[color as usize].get(row, col))
+                .map_or('?', Color::letter)
         }
     }
 
-    pub fn is_valid(&self) -> bool {
+    /// Checks that the masks partition the board, that the territories are connected to their
+    /// starting corners and that the last moves match the round.
+    pub fn validate(&self) -> Result<(), StateError> {
+        if self.round > ROUND_LIMIT {
+            return Err(StateError::RoundLimit);
+        }
         if (self.round >= 1) != self.player1_last_move.is_some()
             || (self.round >= 2) != self.player2_last_move.is_some()
-            || self.round > ROUND_LIMIT
+            || (self.player1_last_move.is_some()
+                && self.player1_last_move == self.player2_last_move)
         {
-            return false;
+            return Err(StateError::LastMoves);
         }
 
-        let mut seen = self.walls;
-
-        macro_rules! check {
-            ($mask:expr) => {{
-                #![allow(unused_assignments)]
-                if !(seen & $mask).is_empty() {
-                    return false;
-                }
-                seen |= $mask;
-            }};
+        if !(self.player1 & self.player2).is_empty()
+            || !((self.player1 | self.player2) & self.walls).is_empty()
+        {
+            return Err(StateError::OverlappingTerritories);
+        }
+        for (player, territory, start) in [
+            (1, self.player1, Mask::one_hot(0, 0)),
+            (2, self.player2, Mask::one_hot(15, 15)),
+        ] {
+            if (territory & start).is_empty() {
+                return Err(StateError::MissingStart(player));
+            }
+            if start.bfs(territory) != territory {
+                return Err(StateError::DisconnectedTerritory(player));
+            }
         }
 
-        check!(self.player1);
-        check!(self.player2);
+        let mut seen = Mask::one_hot(0, 0) | Mask::one_hot(15, 15);
+        for mask in self.colors.into_iter().chain([self.walls]) {
+            if !(seen & mask).is_empty() {
+                return Err(StateError::OverlappingColors);
+            }
+            seen |= mask;
+        }
+        if !seen.is_full() {
+            return Err(StateError::UncoveredCells);
+        }
+        Ok(())
+    }
 
-        seen = Mask::one_hot(0, 0) | Mask::one_hot(15, 15);
+    pub fn is_valid(&self) -> bool {
+        self.validate().is_ok()
+    }
 
-        for color in self.colors {
-            check!(color);
+    /// Plays `color` if it is a legal move, for moves from players that cannot be trusted.
+    pub fn try_play(&mut self, color: Color) -> Result<(), MoveError> {
+        if self.game_over() {
+            Err(MoveError::GameOver)
+        } else if Some(color) == self.player1_last_move || Some(color) == self.player2_last_move {
+            Err(MoveError::Forbidden(color))
+        } else {
+            self.play(color);
+            Ok(())
         }
-        check!(self.walls);
-
-        seen.is_full()
     }
 
+    /// Plays `color` for the player to move. The move must be valid.
     pub fn play(&mut self, color: Color) {
         debug_assert!(self.is_valid());
         debug_assert!(!self.game_over());
-        debug_assert!(Some(color) != self.player1_last_move);
-        debug_assert!(Some(color) != self.player2_last_move);
+        debug_assert_ne!(Some(color), self.player1_last_move);
+        debug_assert_ne!(Some(color), self.player2_last_move);
 
         if self.player1_next() {
             self.player1 = self
@@ -230,6 +307,7 @@ impl State {
         self.round += 1;
     }
 
+    /// Saves what `play` changes, so that `restore` can undo any number of moves.
     pub fn checkpoint(&self) -> Checkpoint {
         Checkpoint {
             players: self.player1 | self.player2,
@@ -240,15 +318,15 @@ impl State {
     }
 
     pub fn restore(&mut self, checkpoint: Checkpoint) {
-        self.player1 = self.player1 & checkpoint.players;
-        self.player2 = self.player2 & checkpoint.players;
+        self.player1 &= checkpoint.players;
+        self.player2 &= checkpoint.players;
         self.player1_last_move = checkpoint.player1_last_move;
         self.player2_last_move = checkpoint.player2_last_move;
         self.round = checkpoint.round;
     }
 
     pub fn player1_next(&self) -> bool {
-        self.round % 2 == 0
+        self.round.is_multiple_of(2)
     }
 
     pub fn game_over(&self) -> bool {
@@ -262,6 +340,7 @@ impl State {
         (player1_accessible & player2_accessible).is_empty()
     }
 
+    /// Player 1's cells minus player 2's, once the game is over.
     pub fn final_margin(&self) -> i32 {
         debug_assert!(self.game_over());
 
@@ -271,6 +350,22 @@ impl State {
         player1.count_ones() as i32 - player2.count_ones() as i32
     }
 
+    /// Splits the cells still in play into those sealed off for player 1, those sealed off for
+    /// player 2 and those both can reach. Sealed cells are a player's territory plus the cells
+    /// only they can reach, which they are sure to end up with.
+    pub fn sealed(&self) -> (Mask, Mask, Mask) {
+        let accessible = !(self.player1 | self.player2 | self.walls);
+        let player1_accessible = self.player1.bfs(accessible);
+        let player2_accessible = self.player2.bfs(accessible);
+        let contested = player1_accessible & player2_accessible;
+        (
+            player1_accessible & !contested,
+            player2_accessible & !contested,
+            contested,
+        )
+    }
+
+    /// Gives each player the cells only they can reach, once the game is over.
     pub fn finalize(&mut self) {
         debug_assert!(self.game_over());
 
@@ -279,6 +374,7 @@ impl State {
         self.player2 = self.player2.bfs(accessible);
     }
 
+    /// The colors the player to move may play.
     pub fn valid_moves(&self) -> ValidMoves {
         ValidMoves {
             next_index: 0,
@@ -286,9 +382,323 @@ impl State {
             player2_last_move: self.player2_last_move,
         }
     }
+
+    /// The position as seen by the player to move, shared by every position equivalent to it.
+    pub fn canonical(&self) -> Canonical {
+        let flip = |mask: Mask| {
+            if self.player1_next() {
+                mask
+            } else {
+                mask.flip()
+            }
+        };
+        let colors = self.colors.map(flip);
+
+        let labels = self.canonical_colors();
+        let mut order = [0; 8];
+        for (old, new) in labels.into_iter().enumerate() {
+            order[new as usize] = old;
+        }
+        let relabel = |color: Option<Color>| color.map(|color| labels[color as usize]);
+
+        let (mover, opponent, mover_last_move, opponent_last_move) = if self.player1_next() {
+            (
+                self.player1,
+                self.player2,
+                self.player1_last_move,
+                self.player2_last_move,
+            )
+        } else {
+            (
+                self.player2.flip(),
+                self.player1.flip(),
+                self.player2_last_move,
+                self.player1_last_move,
+            )
+        };
+
+        Canonical {
+            colors: order.map(|i| colors[i]),
+            walls: flip(self.walls),
+            mover,
+            opponent,
+            mover_last_move: relabel(mover_last_move),
+            opponent_last_move: relabel(opponent_last_move),
+        }
+    }
+
+    /// The label each color gets in `canonical`, indexed by `Color as usize`.
+    pub fn canonical_colors(&self) -> [Color; 8] {
+        let first = |mask: Mask| {
+            let mask = if self.player1_next() {
+                mask
+            } else {
+                mask.flip()
+            };
+            mask.lowest().unwrap_or(256)
+        };
+        let mut order = [0, 1, 2, 3, 4, 5, 6, 7];
+        order.sort_by_key(|&i| (first(self.colors[i]), i));
+        let mut labels = [Color::Red; 8];
+        for (new, old) in order.into_iter().enumerate() {
+            labels[old] = Color::LIST[new];
+        }
+        labels
+    }
+
+    /// A hash of `canonical`, the same on every platform and `Mask` backend.
+    pub fn canonical_hash(&self) -> u64 {
+        self.canonical().hash()
+    }
+
+    /// Like `canonical_hash`, but without the symmetries: only positions that differ in nothing
+    /// but the round share it.
+    pub fn position_hash(&self) -> u64 {
+        let (mover, opponent, mover_last_move, opponent_last_move) = if self.player1_next() {
+            (
+                self.player1,
+                self.player2,
+                self.player1_last_move,
+                self.player2_last_move,
+            )
+        } else {
+            (
+                self.player2,
+                self.player1,
+                self.player2_last_move,
+                self.player1_last_move,
+            )
+        };
+        Canonical {
+            colors: self.colors,
+            walls: self.walls,
+            mover,
+            opponent,
+            mover_last_move,
+            opponent_last_move,
+        }
+        .hash()
+    }
+}
+
+/// Why a `State` is not a position of the game, from `State::validate`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateError {
+    /// The round is past `ROUND_LIMIT`.
+    RoundLimit,
+    /// The last moves do not match the round, or are the same color.
+    LastMoves,
+    /// The territories overlap each other or the walls.
+    OverlappingTerritories,
+    /// The territory of player 1 or 2 does not contain their starting corner.
+    MissingStart(u8),
+    /// The territory of player 1 or 2 is not connected.
+    DisconnectedTerritory(u8),
+    /// The colors overlap each other, the walls or the starting corners.
+    OverlappingColors,
+    /// Some cells other than the starting corners have no color and are not walls.
+    UncoveredCells,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::RoundLimit => write!(f, "round past the limit of {ROUND_LIMIT}"),
+            StateError::LastMoves => f.write_str("last moves do not match the round"),
+            StateError::OverlappingTerritories => f.write_str("overlapping territories"),
+            StateError::MissingStart(player) => {
+                write!(f, "player {player} does not own their starting corner")
+            }
+            StateError::DisconnectedTerritory(player) => {
+                write!(f, "player {player}'s territory is not connected")
+            }
+            StateError::OverlappingColors => f.write_str("overlapping colors"),
+            StateError::UncoveredCells => f.write_str("cells without a color"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+/// Why a move cannot be played, from `State::try_play`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveError {
+    GameOver,
+    /// The color is the last move of one of the players.
+    Forbidden(Color),
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::GameOver => f.write_str("the game is over"),
+            MoveError::Forbidden(color) => write!(f, "{color} was just played"),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// One line per row, with `X` for walls, `1` and `2` for the players' territories and
+/// `Color::letter` for the other cells.
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in 0..16 {
+            if row > 0 {
+                writeln!(f)?;
+            }
+            for col in 0..16 {
+                write!(f, "{}", self.symbol(row, col))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A `State` displayed with ANSI colored backgrounds, from `State::colored`.
+pub struct Colored<'a>(&'a State);
+
+impl fmt::Display for Colored<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.0;
+        for row in 0..16 {
+            for col in 0..16 {
+                match state.symbol(row, col) {
+                    'X' => write!(f, "{}", Bg(Black)),
+                    '1' => write!(f, "{}", Bg(White)),
+                    '2' => write!(f, "{}", Bg(LightBlack)),
+                    'r' => write!(f, "{}", Bg(Red)),
+                    'o' => write!(f, "{}", Bg(LightRed)),
+                    'y' => write!(f, "{}", Bg(Yellow)),
+                    'g' => write!(f, "{}", Bg(Green)),
+                    'c' => write!(f, "{}", Bg(Cyan)),
+                    'b' => write!(f, "{}", Bg(Blue)),
+                    'p' => write!(f, "{}", Bg(Magenta)),
+                    'k' => write!(f, "{}", Bg(LightMagenta)),
+                    _ => write!(f, "{}", Bg(Reset)),
+                }?;
+                f.write_str("  ")?;
+            }
+            writeln!(f, "{}", Bg(Reset))?;
+        }
+        Ok(())
+    }
+}
+
+/// A position that searches can play moves on and take back, however it is represented.
+pub trait Position {
+    type Checkpoint: Copy;
+
+    fn state(&self) -> &State;
+    fn play(&mut self, color: Color);
+    fn checkpoint(&self) -> Self::Checkpoint;
+    fn restore(&mut self, checkpoint: Self::Checkpoint);
+}
+
+impl Position for State {
+    type Checkpoint = Checkpoint;
+
+    fn state(&self) -> &State {
+        self
+    }
+
+    fn play(&mut self, color: Color) {
+        State::play(self, color);
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        State::checkpoint(self)
+    }
+
+    fn restore(&mut self, checkpoint: Checkpoint) {
+        State::restore(self, checkpoint);
+    }
+}
+
+/// Counts of the game tree below a position, from `perft`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Perft {
+    /// Positions exactly `depth` moves deep where the game is not over.
+    pub leaves: u64,
+    /// Positions at most `depth` moves deep where the game is over.
+    pub terminal: u64,
+    /// The sum of the final margins of the terminal positions.
+    pub margin: i64,
+    /// The sum over the leaves of player 1's cells minus player 2's.
+    pub territory: i64,
+}
+
+/// Walks every line of play from `position` up to `depth` moves deep. The counts only change if
+/// the rules do, which makes them a check on `play`, `restore`, `valid_moves` and `game_over`.
+pub fn perft<P: Position>(position: &mut P, depth: u32) -> Perft {
+    let state = position.state();
+    if state.game_over() {
+        return Perft {
+            terminal: 1,
+            margin: i64::from(state.final_margin()),
+            ..Perft::default()
+        };
+    } else if depth == 0 {
+        return Perft {
+            leaves: 1,
+            territory: i64::from(state.player1.count_ones())
+                - i64::from(state.player2.count_ones()),
+            ..Perft::default()
+        };
+    }
+
+    let mut total = Perft::default();
+    let checkpoint = position.checkpoint();
+    for color in state.valid_moves() {
+        position.play(color);
+        let below = perft(position, depth - 1);
+        position.restore(checkpoint);
+
+        total.leaves += below.leaves;
+        total.terminal += below.terminal;
+        total.margin += below.margin;
+        total.territory += below.territory;
+    }
+    total
+}
+
+/// A position that counts the moves played on it, which is the number of nodes a search visits
+/// besides the root.
+#[derive(Clone, Copy, Debug)]
+pub struct Counted<P> {
+    pub inner: P,
+    pub nodes: u64,
+}
+
+impl<P> Counted<P> {
+    pub fn new(inner: P) -> Self {
+        Self { inner, nodes: 0 }
+    }
+}
+
+impl<P: Position> Position for Counted<P> {
+    type Checkpoint = P::Checkpoint;
+
+    fn state(&self) -> &State {
+        self.inner.state()
+    }
+
+    fn play(&mut self, color: Color) {
+        self.nodes += 1;
+        self.inner.play(color);
+    }
+
+    fn checkpoint(&self) -> P::Checkpoint {
+        self.inner.checkpoint()
+    }
+
+    fn restore(&mut self, checkpoint: P::Checkpoint) {
+        self.inner.restore(checkpoint);
+    }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Checkpoint {
     pub players: Mask,
     pub player1_last_move: Option<Color>,
@@ -316,3 +726,371 @@ impl Iterator for ValidMoves {
         Color::LIST.get(self.next_index - 1).copied()
     }
 }
+
+/// A position from the point of view of the player to move. The board is rotated so that they
+/// start in the top left corner, and the colors are relabeled in the order they first appear.
+///
+/// Positions that only differ by these symmetries have the same canonical form. The round is left
+/// out, so positions that differ in how close they are to `ROUND_LIMIT` share it too: they only
+/// have the same value for the player to move while the limit is out of reach, and anything
+/// keyed on the canonical form must check that itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Canonical {
+    pub colors: [Mask; 8],
+    pub walls: Mask,
+    pub mover: Mask,
+    pub opponent: Mask,
+    pub mover_last_move: Option<Color>,
+    pub opponent_last_move: Option<Color>,
+}
+
+impl Canonical {
+    /// A 64-bit hash that only depends on the position, unlike the one from `Hash`.
+    pub fn hash(&self) -> u64 {
+        fn mix(hash: u64, value: u64) -> u64 {
+            (hash.rotate_left(5) ^ value).wrapping_mul(0x517c_c1b7_2722_0a95)
+        }
+
+        let last_move = |color: Option<Color>| color.map_or(8, |color| color as u64);
+        let mut hash = mix(
+            0,
+            last_move(self.mover_last_move) << 4 | last_move(self.opponent_last_move),
+        );
+        for mask in self
+            .colors
+            .iter()
+            .chain([&self.walls, &self.mover, &self.opponent])
+        {
+            for rows in <[u16; 16]>::from(*mask).chunks_exact(4) {
+                let word = rows
+                    .iter()
+                    .rev()
+                    .fold(0, |word, &row| word << 16 | u64::from(row));
+                hash = mix(hash, word);
+            }
+        }
+
+        // Spread the low bits, which the multiplications above leave poorly mixed.
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        hash ^ (hash >> 33)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use rand::prelude::*;
+    use rand_pcg::Pcg64;
+
+    use super::*;
+
+    const GAMES: u64 = 200;
+
+    #[test]
+    fn display() {
+        let mut state = State::generate(0);
+        state.walls = Mask::from_cells((0..4).map(|col| (7, col)));
+        state.player1 = Mask::from_cells([(0, 0), (0, 1)]);
+        state.player2 = Mask::one_hot(15, 15);
+        let rest = !(state.walls | state.player1 | state.player2);
+        for color in Color::LIST {
+            let columns =
+                (0..16).flat_map(|row| [(row, color as usize), (row, color as usize + 8)]);
+            state.colors[color as usize] = rest & Mask::from_cells(columns);
+        }
+
+        let mut expected = vec!["roygcbpkroygcbpk"; 16];
+        expected[0] = "11ygcbpkroygcbpk";
+        expected[7] = "XXXXcbpkroygcbpk";
+        expected[15] = "roygcbpkroygcbp2";
+        assert_eq!(state.to_string(), expected.join("\n"));
+    }
+
+    /// Every position of a game where both players pick random moves.
+    fn random_game(seed: u64) -> Vec<State> {
+        let mut rng = Pcg64::seed_from_u64(seed);
+        let mut state = State::generate(seed);
+        let mut states = vec![state];
+        while !state.game_over() {
+            state.play(state.valid_moves().choose(&mut rng).unwrap());
+            states.push(state);
+        }
+        states
+    }
+
+    /// The same position with the board rotated and the players swapped.
+    fn swapped(state: &State) -> State {
+        State {
+            colors: state.colors.map(Mask::flip),
+            walls: state.walls.flip(),
+            player1: state.player2.flip(),
+            player2: state.player1.flip(),
+            ..*state
+        }
+    }
+
+    /// The same position with the board rotated, the players swapped and the colors relabeled
+    /// by `labels`, one round later so that the same side is to move.
+    pub(crate) fn twin(state: &State, labels: [Color; 8]) -> State {
+        let relabel = |color: Option<Color>| color.map(|color| labels[color as usize]);
+        let mut colors = [Mask::empty(); 8];
+        for (color, mask) in state.colors.into_iter().enumerate() {
+            colors[labels[color] as usize] = mask.flip();
+        }
+        State {
+            colors,
+            player1_last_move: relabel(state.player2_last_move),
+            player2_last_move: relabel(state.player1_last_move),
+            round: state.round + 1,
+            ..swapped(state)
+        }
+    }
+
+    #[test]
+    fn twins_share_canonical_form() {
+        let mut rng = Pcg64::seed_from_u64(0);
+        for seed in 0..GAMES {
+            for state in random_game(seed).iter().filter(|state| state.round >= 2) {
+                let mut labels = Color::LIST;
+                labels.shuffle(&mut rng);
+                let twin = twin(state, labels);
+                assert_ne!(twin.player1_next(), state.player1_next());
+                assert_eq!(twin.canonical(), state.canonical());
+                assert_eq!(twin.canonical_hash(), state.canonical_hash());
+                assert_ne!(twin.position_hash(), state.position_hash());
+
+                let later = State {
+                    round: state.round + 2,
+                    ..*state
+                };
+                assert_eq!(later.position_hash(), state.position_hash());
+
+                let mut other = *state;
+                other.player1 = other.player1.shift_right() | other.player1;
+                if other.player1 != state.player1 {
+                    assert_ne!(other.canonical_hash(), state.canonical_hash());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn generated_states_are_valid() {
+        for seed in 0..1000 {
+            let state = State::generate(seed);
+            assert_eq!(state.validate(), Ok(()), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn validate_errors() {
+        let state = random_game(3)[6];
+        assert_eq!(state.validate(), Ok(()));
+        let broken = |change: &dyn Fn(&mut State)| {
+            let mut broken = state;
+            change(&mut broken);
+            broken.validate()
+        };
+        let free = !(state.player1 | state.player2 | state.walls);
+        let far = (free & !state.player1.neighbors()).lowest().unwrap();
+        let colored = (state.colors[0] & free).lowest().unwrap();
+
+        assert_eq!(
+            broken(&|state| state.round = ROUND_LIMIT + 1),
+            Err(StateError::RoundLimit)
+        );
+        assert_eq!(
+            broken(&|state| state.player2_last_move = None),
+            Err(StateError::LastMoves)
+        );
+        assert_eq!(broken(&|state| state.round = 0), Err(StateError::LastMoves));
+        assert_eq!(
+            broken(&|state| state.player1_last_move = state.player2_last_move),
+            Err(StateError::LastMoves)
+        );
+        assert_eq!(
+            broken(&|state| state.player2 |= Mask::one_hot(0, 0)),
+            Err(StateError::OverlappingTerritories)
+        );
+        assert_eq!(
+            broken(&|state| state.walls |= Mask::one_hot(15, 15)),
+            Err(StateError::OverlappingTerritories)
+        );
+        assert_eq!(
+            broken(&|state| state.player1 &= !Mask::one_hot(0, 0)),
+            Err(StateError::MissingStart(1))
+        );
+        assert_eq!(
+            broken(&|state| state.player2 &= !Mask::one_hot(15, 15)),
+            Err(StateError::MissingStart(2))
+        );
+        assert_eq!(
+            broken(&|state| state.player1 |= Mask::from_index(far)),
+            Err(StateError::DisconnectedTerritory(1))
+        );
+        assert_eq!(
+            broken(&|state| state.colors[1] |= Mask::from_index(colored)),
+            Err(StateError::OverlappingColors)
+        );
+        assert_eq!(
+            broken(&|state| state.colors[0] |= Mask::one_hot(0, 0)),
+            Err(StateError::OverlappingColors)
+        );
+        assert_eq!(
+            broken(&|state| state.colors[0] &= !Mask::from_index(colored)),
+            Err(StateError::UncoveredCells)
+        );
+    }
+
+    #[test]
+    fn try_play_errors() {
+        let states = random_game(3);
+        let mut state = states[6];
+        let forbidden = [state.player1_last_move, state.player2_last_move].map(Option::unwrap);
+        for color in forbidden {
+            assert_eq!(state.try_play(color), Err(MoveError::Forbidden(color)));
+            assert_eq!(state, states[6]);
+        }
+        let color = state.valid_moves().next().unwrap();
+        assert_eq!(state.try_play(color), Ok(()));
+        assert_eq!(state.round, 7);
+
+        let mut state = *states.last().unwrap();
+        assert!(state.game_over());
+        for color in Color::LIST {
+            assert_eq!(state.try_play(color), Err(MoveError::GameOver));
+        }
+        assert_eq!(state, *states.last().unwrap());
+    }
+
+    #[test]
+    fn restore_undoes_play() {
+        for seed in 0..GAMES {
+            for state in random_game(seed)
+                .into_iter()
+                .filter(|state| !state.game_over())
+            {
+                let checkpoint = state.checkpoint();
+                for color in state.valid_moves() {
+                    let mut next = state;
+                    next.play(color);
+                    assert_eq!(next.validate(), Ok(()));
+                    next.restore(checkpoint);
+                    assert_eq!(next, state);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn territories_only_grow() {
+        for seed in 0..GAMES {
+            for pair in random_game(seed).windows(2) {
+                let [before, after] = pair else {
+                    unreachable!()
+                };
+                assert_eq!(after.player1 & before.player1, before.player1);
+                assert_eq!(after.player2 & before.player2, before.player2);
+                assert_eq!(after.round, before.round + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn sealed_cells_stay_sealed() {
+        for seed in 0..GAMES {
+            let states = random_game(seed);
+            for pair in states.windows(2) {
+                let [before, after] = pair else {
+                    unreachable!()
+                };
+                let (player1, player2, _) = before.sealed();
+                let (player1_after, player2_after, _) = after.sealed();
+                assert_eq!(player1_after & player1, player1);
+                assert_eq!(player2_after & player2, player2);
+            }
+
+            let state = states.last().unwrap();
+            let (player1, player2, contested) = state.sealed();
+            assert!(contested.is_empty() || state.round == ROUND_LIMIT);
+            assert_eq!(
+                player1.count_ones() as i32 - player2.count_ones() as i32,
+                state.final_margin()
+            );
+        }
+    }
+
+    #[test]
+    fn final_margin_is_antisymmetric() {
+        for seed in 0..GAMES {
+            let state = *random_game(seed).last().unwrap();
+            let swapped = swapped(&state);
+            assert!(swapped.game_over());
+            assert_eq!(swapped.final_margin(), -state.final_margin());
+        }
+    }
+
+    /// The position `before_end` moves before the end of a game where each player captures as
+    /// many cells as they can, taking the first such color.
+    fn late_position(seed: u64, before_end: usize) -> State {
+        let mut state = State::generate(seed);
+        let mut states = vec![state];
+        while !state.game_over() {
+            let color = state
+                .valid_moves()
+                .min_by_key(|&color| {
+                    let mut next = state;
+                    next.play(color);
+                    core::cmp::Reverse(next.player1.count_ones() + next.player2.count_ones())
+                })
+                .unwrap();
+            state.play(color);
+            states.push(state);
+        }
+        states[states.len().saturating_sub(before_end + 1)]
+    }
+
+    fn counts(leaves: u64, terminal: u64, margin: i64, territory: i64) -> Perft {
+        Perft {
+            leaves,
+            terminal,
+            margin,
+            territory,
+        }
+    }
+
+    #[test]
+    fn perft_from_start() {
+        let expected = [
+            counts(2016, 0, 0, 1152),
+            counts(2016, 0, 0, 36),
+            counts(2016, 0, 0, 504),
+            counts(2016, 0, 0, 1584),
+        ];
+        for (seed, expected) in (0..).zip(expected) {
+            assert_eq!(
+                perft(&mut State::generate(seed), 4),
+                expected,
+                "seed {seed}"
+            );
+        }
+    }
+
+    #[test]
+    fn perft_near_end() {
+        let expected = [
+            counts(46489, 127, -944, 185_126),
+            counts(46455, 151, 4048, 589_297),
+            counts(24935, 5416, 179_152, 649_278),
+            counts(45670, 506, 9030, 891_452),
+        ];
+        for (seed, expected) in (0..).zip(expected) {
+            assert_eq!(
+                perft(&mut late_position(seed, 6), 6),
+                expected,
+                "seed {seed}"
+            );
+        }
+    }
+}
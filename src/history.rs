@@ -0,0 +1,171 @@
+//! A game with every move kept, so that moves can be undone and redone in any order.
+
+use crate::game::{Color, MoveError, State};
+use crate::mask::Mask;
+
+/// A move together with the cells it captured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Move {
+    pub color: Color,
+    pub captured: Mask,
+}
+
+/// A game from some starting position, with the moves played since then.
+///
+/// Unlike `State::restore`, undoing does not need a checkpoint, since every move remembers what
+/// it captured. Undone moves are kept until a different move is played, and can be redone.
+#[derive(Clone, Debug)]
+pub struct GameHistory {
+    start: State,
+    state: State,
+    /// The moves played from `start`, including the undone ones after `current`.
+    moves: Vec<Move>,
+    /// The number of moves that are not undone.
+    current: usize,
+}
+
+impl GameHistory {
+    pub fn new(start: State) -> Self {
+        Self {
+            start,
+            state: start,
+            moves: Vec::new(),
+            current: 0,
+        }
+    }
+
+    /// Plays the moves of a game from the board generated from `seed`.
+    pub fn replay(seed: u64, colors: impl IntoIterator<Item = Color>) -> Result<Self, MoveError> {
+        let mut history = Self::new(State::generate(seed));
+        for color in colors {
+            history.play(color)?;
+        }
+        Ok(history)
+    }
+
+    pub fn start(&self) -> &State {
+        &self.start
+    }
+
+    /// The position after the moves that are not undone.
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// The moves that are not undone, in the order they were played.
+    pub fn moves(&self) -> &[Move] {
+        &self.moves[..self.current]
+    }
+
+    /// The moves that were undone and can be redone, next one first.
+    pub fn undone(&self) -> &[Move] {
+        &self.moves[self.current..]
+    }
+
+    /// Plays `color` if it is legal, forgetting the undone moves, and returns what it captured.
+    pub fn play(&mut self, color: Color) -> Result<Mask, MoveError> {
+        let before = self.state.player1 | self.state.player2;
+        self.state.try_play(color)?;
+        let captured = (self.state.player1 | self.state.player2) & !before;
+
+        self.moves.truncate(self.current);
+        self.moves.push(Move { color, captured });
+        self.current += 1;
+        Ok(captured)
+    }
+
+    /// Takes back the last move, if there is one.
+    pub fn undo(&mut self) -> Option<Move> {
+        self.current = self.current.checked_sub(1)?;
+        let last = self.moves[self.current];
+
+        self.state.round -= 1;
+        let player1 = self.state.player1_next();
+
+        // The mover's previous move is two moves back, or from before the history started.
+        let previous = match self.current.checked_sub(2) {
+            Some(index) => Some(self.moves[index].color),
+            None if player1 => self.start.player1_last_move,
+            None => self.start.player2_last_move,
+        };
+        if player1 {
+            self.state.player1 &= !last.captured;
+            self.state.player1_last_move = previous;
+        } else {
+            self.state.player2 &= !last.captured;
+            self.state.player2_last_move = previous;
+        }
+        Some(last)
+    }
+
+    /// Plays the last undone move again, if there is one.
+    pub fn redo(&mut self) -> Option<Move> {
+        let next = *self.moves.get(self.current)?;
+        self.state.play(next.color);
+        self.current += 1;
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_pcg::Pcg64;
+
+    use super::*;
+
+    /// Plays random moves to the end of the game, returning every position on the way.
+    fn play_out(history: &mut GameHistory, rng: &mut impl Rng) -> Vec<State> {
+        let mut states = vec![*history.state()];
+        while !history.state().game_over() {
+            let color = history.state().valid_moves().choose(rng).unwrap();
+            history.play(color).unwrap();
+            states.push(*history.state());
+        }
+        states
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut rng = Pcg64::seed_from_u64(0);
+        for seed in 0..50 {
+            let mut start = State::generate(seed);
+            // Every other game starts mid-game, with last moves from before the history.
+            for _ in 0..(seed % 2) * 7 {
+                if !start.game_over() {
+                    start.play(start.valid_moves().choose(&mut rng).unwrap());
+                }
+            }
+
+            let mut history = GameHistory::new(start);
+            let states = play_out(&mut history, &mut rng);
+            for state in states.iter().rev().skip(1) {
+                assert!(history.undo().is_some());
+                assert_eq!(history.state(), state);
+            }
+            assert!(history.undo().is_none());
+            assert_eq!(history.undone().len(), states.len() - 1);
+
+            for state in &states[1..] {
+                assert!(history.redo().is_some());
+                assert_eq!(history.state(), state);
+            }
+            assert!(history.redo().is_none());
+
+            // Playing after undoing forgets the undone moves.
+            let undone = (states.len() - 1) / 2;
+            for _ in 0..undone {
+                history.undo();
+            }
+            if !history.state().game_over() {
+                play_out(&mut history, &mut rng);
+                assert!(history.undone().is_empty());
+                assert_eq!(
+                    history.moves().len() as u32,
+                    history.state().round - start.round
+                );
+            }
+        }
+    }
+}
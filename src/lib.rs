@@ -0,0 +1,46 @@
+//! An engine for a two player version of Flood-It.
+//!
+//! Two players start in opposite corners of a 16x16 board of colored cells and walls. On their
+//! turn, a player picks a color and captures every cell of that color connected to their
+//! territory. A player may not pick the color either player picked last. The game ends when no
+//! cell can be reached by both players, or after `game::ROUND_LIMIT` moves, and the player with
+//! the most cells wins.
+//!
+//! - [`Mask`] is a 16x16 bitboard, with SIMD and scalar backends.
+//! - [`State`] holds a position and applies the rules.
+//! - [`region::RegionGraph`] describes the board as adjacent regions of one color.
+//! - [`Player`]s choose moves, usually by searching with [`negamax`] and an [`Eval`].
+//! - [`arena`] plays games between players and keeps score.
+//!
+//! The default `simd` feature needs a nightly toolchain. Without it, the crate builds on stable.
+
+#![warn(clippy::pedantic)]
+#![allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_possible_wrap,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss,
+    clippy::missing_errors_doc,
+    clippy::missing_panics_doc,
+    clippy::must_use_candidate,
+    clippy::return_self_not_must_use
+)]
+#![cfg_attr(feature = "simd", feature(portable_simd))]
+
+pub mod arena;
+pub mod bench;
+pub mod book;
+pub mod bot;
+pub mod clock;
+pub mod datagen;
+pub mod engine;
+pub mod game;
+pub mod history;
+pub mod mask;
+pub mod nn;
+pub mod region;
+pub mod tune;
+
+pub use bot::{Eval, Player, negamax};
+pub use game::{Color, State};
+pub use mask::Mask;
@@ -1,277 +1,944 @@
-use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
-use core::simd::{self, ToBytes, prelude::*};
+//! Bitboards for the 16x16 board.
+//!
+//! Cells are addressed by `(row, col)`, or by their index `16 * row + col`.
+
+/// Operations written in terms of the primitives every backend provides, so that all backends
+/// share a single implementation of them.
+macro_rules! shared {
+    () => {
+        impl Mask {
+            /// The cells next to the mask, not in it.
+            pub fn neighbors(self) -> Self {
+                (self.shift_left() | self.shift_right() | self.shift_up() | self.shift_down())
+                    & !self
+            }
 
-use rand::Rng;
+            /// The cells at most two steps from the mask, not in it.
+            pub fn neighbors2(self) -> Self {
+                let neighbors =
+                    self.shift_left() | self.shift_right() | self.shift_up() | self.shift_down();
+                (neighbors
+                    | neighbors.shift_left()
+                    | neighbors.shift_right()
+                    | neighbors.shift_up()
+                    | neighbors.shift_down())
+                    & !self
+            }
 
-#[repr(transparent)]
-#[derive(Clone, Copy, PartialEq)]
-pub struct Mask(u16x16);
+            pub fn is_empty(self) -> bool {
+                self == Self::empty()
+            }
 
-impl Mask {
-    pub const fn empty() -> Self {
-        Self(u16x16::splat(0))
-    }
+            pub fn is_full(self) -> bool {
+                self == Self::full()
+            }
 
-    pub const fn full() -> Self {
-        Self(u16x16::splat(u16::MAX))
-    }
+            /// Rotates the board a quarter turn clockwise.
+            pub fn rotate_90(self) -> Self {
+                self.transpose().flip_horizontal()
+            }
 
-    pub fn one_hot(row: usize, col: usize) -> Self {
-        debug_assert!(row < 16, "row out of bounds");
-        debug_assert!(col < 16, "col out of bounds");
+            /// Rotates the board a quarter turn counterclockwise.
+            pub fn rotate_270(self) -> Self {
+                self.transpose().flip_vertical()
+            }
 
-        let mut array = [0u16; 16];
-        array[row] = 1 << col;
-        Self(u16x16::from_array(array))
-    }
+            /// Moves every cell one row down and one column right, along the main diagonal.
+            pub fn shift_diagonal(self) -> Self {
+                self.shift_down().shift_left()
+            }
 
-    pub fn shift_left(self) -> Self {
-        Self(self.0 << 1)
-    }
+            /// Rotates the board by 180 degrees.
+            pub fn flip(self) -> Self {
+                self.flip_horizontal().flip_vertical()
+            }
 
-    pub fn shift_right(self) -> Self {
-        Self(self.0 >> 1)
-    }
+            /// The mask of the cell with the given index.
+            pub fn from_index(index: usize) -> Self {
+                debug_assert!(index < 256, "index out of bounds");
 
-    pub fn shift_up(self) -> Self {
-        Self(self.0.shift_elements_left::<1>(0))
-    }
+                let mut words = [0; 4];
+                words[index / 64] = 1 << (index % 64);
+                Self::from_words(words)
+            }
 
-    pub fn shift_down(self) -> Self {
-        Self(self.0.shift_elements_right::<1>(0))
-    }
+            pub fn from_cells(cells: impl IntoIterator<Item = (usize, usize)>) -> Self {
+                Self::from_indices(cells.into_iter().map(|(row, col)| 16 * row + col))
+            }
 
-    pub fn neighbors(self) -> Self {
-        (self.shift_left() | self.shift_right() | self.shift_up() | self.shift_down()) & !self
-    }
+            pub fn from_indices(indices: impl IntoIterator<Item = usize>) -> Self {
+                let mut words = [0u64; 4];
+                for index in indices {
+                    debug_assert!(index < 256, "index out of bounds");
+                    words[index / 64] |= 1 << (index % 64);
+                }
+                Self::from_words(words)
+            }
 
-    pub fn neighbors2(self) -> Self {
-        let neighbors =
-            self.shift_left() | self.shift_right() | self.shift_up() | self.shift_down();
-        (neighbors
-            | neighbors.shift_left()
-            | neighbors.shift_right()
-            | neighbors.shift_up()
-            | neighbors.shift_down())
-            & !self
-    }
+            /// The indices of the cells in the mask, in increasing order.
+            pub fn indices(self) -> $crate::mask::Indices {
+                $crate::mask::Indices(self.to_words())
+            }
 
-    pub fn count_ones(self) -> u32 {
-        self.0.count_ones().reduce_sum() as u32
-    }
+            /// The `(row, col)` of the cells in the mask, in increasing order.
+            pub fn cells(self) -> impl ExactSizeIterator<Item = (usize, usize)> {
+                self.indices().map(|index| (index / 16, index % 16))
+            }
 
-    pub fn is_empty(self) -> bool {
-        self == Self::empty()
-    }
+            /// The index of the first cell, if any.
+            pub fn lowest(self) -> Option<usize> {
+                self.indices().next()
+            }
 
-    pub fn is_full(self) -> bool {
-        self == Self::full()
-    }
+            /// Removes the first cell and returns its index.
+            pub fn pop_lowest(&mut self) -> Option<usize> {
+                let mut indices = self.indices();
+                let index = indices.next()?;
+                *self = Self::from_words(indices.0);
+                Some(index)
+            }
+
+            /// The number of cells in each column.
+            pub fn col_counts(self) -> [u32; 16] {
+                const LOW: u64 = 0x0001_0001_0001_0001;
+                let words = self.to_words();
+                core::array::from_fn(|col| {
+                    words
+                        .iter()
+                        .map(|word| (word & (LOW << col)).count_ones())
+                        .sum()
+                })
+            }
+
+            pub fn random(rng: &mut impl rand::Rng) -> Self {
+                let mut array: [u16; 16] = [0; 16];
+                rng.fill(&mut array);
+                array.into()
+            }
+
+            /// Parses the 64 hex digit format written by `{:x}`: one 4 digit group per row, top
+            /// first.
+            pub fn from_hex(s: &str) -> Option<Self> {
+                if s.len() != 64 || !s.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                    return None;
+                }
+                let mut array = [0u16; 16];
+                for (row, digits) in array.iter_mut().zip(s.as_bytes().chunks_exact(4)) {
+                    *row = u16::from_str_radix(core::str::from_utf8(digits).ok()?, 16).ok()?;
+                }
+                Some(array.into())
+            }
+
+            /// Grows the mask through the `accessible` cells connected to it.
+            pub fn bfs(mut self, accessible: Self) -> Self {
+                loop {
+                    let captured = self.neighbors() & accessible;
+                    if captured.is_empty() {
+                        return self;
+                    }
+                    self |= captured;
+                }
+            }
+
+            /// The rings of `accessible` cells at distance 1, 2, ... from the mask, moving
+            /// through `accessible` cells.
+            pub fn layers(self, accessible: Self) -> Vec<Self> {
+                let mut seen = self;
+                core::iter::from_fn(|| {
+                    let ring = seen.neighbors() & accessible;
+                    seen |= ring;
+                    (!ring.is_empty()).then_some(ring)
+                })
+                .collect()
+            }
+
+            /// Like `layers`, but counting color moves: ring `k` holds the cells that can be
+            /// captured in `k` moves and no fewer, where a move captures the cells of one color
+            /// connected to what was captured before.
+            pub fn color_layers(self, accessible: Self, colors: &[Mask; 8]) -> Vec<Self> {
+                let mut seen = self;
+                core::iter::from_fn(|| {
+                    let mut ring = Self::empty();
+                    for color in colors {
+                        ring |= seen.bfs(*color & accessible);
+                    }
+                    ring &= !seen;
+                    seen |= ring;
+                    (!ring.is_empty()).then_some(ring)
+                })
+                .collect()
+            }
+
+            /// The distance of every cell from the mask through `accessible` cells, or `None` if
+            /// it cannot be reached.
+            pub fn distances(self, accessible: Self) -> [[Option<u8>; 16]; 16] {
+                self.distance_grid(&self.layers(accessible))
+            }
+
+            /// The number of color moves needed to capture every cell, as in `color_layers`.
+            pub fn color_distances(
+                self,
+                accessible: Self,
+                colors: &[Mask; 8],
+            ) -> [[Option<u8>; 16]; 16] {
+                self.distance_grid(&self.color_layers(accessible, colors))
+            }
+
+            fn distance_grid(self, layers: &[Self]) -> [[Option<u8>; 16]; 16] {
+                let mut grid = [[None; 16]; 16];
+                for (distance, ring) in core::iter::once(self)
+                    .chain(layers.iter().copied())
+                    .enumerate()
+                {
+                    for (row, col) in ring.cells() {
+                        grid[row][col] = Some(distance as u8);
+                    }
+                }
+                grid
+            }
 
-    pub fn score(self, scores: &[[u8; 16]; 16]) -> u32 {
-        let mut sum = 0;
-        let array = self.0.as_array();
-        for i in 0..16 {
-            let row = u8x16::from_array(scores[i]);
-            let mask = simd::Mask::<i8, 16>::from_bitmask(array[i] as u64);
-            let select = mask.select(row, u8x16::splat(0));
-            sum += select.cast::<u16>().reduce_sum();
+            /// The 4-connected regions of the mask, in the order of their first cell.
+            pub fn components(self) -> impl Iterator<Item = Self> {
+                let mut rest = self;
+                core::iter::from_fn(move || {
+                    let component = Self::from_index(rest.lowest()?).bfs(rest);
+                    rest &= !component;
+                    Some(component)
+                })
+            }
+
+            /// Splits the cells reachable through non-wall cells by which of `self` and `other` is
+            /// strictly closer. Cells at equal distance go to neither.
+            pub fn closer(mut self, mut other: Self, walls: Self) -> (Self, Self) {
+                let mut accessible = !(self | other | walls);
+                loop {
+                    let self_neighbors = self.neighbors() & accessible & !other;
+                    let other_neighbors = other.neighbors() & accessible & !self;
+
+                    if self_neighbors.is_empty() && other_neighbors.is_empty() {
+                        return (self, other);
+                    }
+
+                    let self_captured = self_neighbors & !other_neighbors;
+                    let other_captured = other_neighbors & !self_neighbors;
+
+                    self |= self_captured;
+                    other |= other_captured;
+                    accessible &= !self_neighbors & !other_neighbors;
+                }
+            }
+
+            /// Like `closer`, but distances count moves, capturing same-colored regions at once.
+            pub fn closer_by_color(
+                mut self,
+                mut other: Self,
+                walls: Self,
+                colors: &[Mask; 8],
+            ) -> (Self, Self) {
+                let mut accessible = !(self | other | walls);
+                loop {
+                    let mut self_neighbors = Self::empty();
+                    let mut other_neighbors = Self::empty();
+                    for color in colors {
+                        self_neighbors |= self.bfs(*color & accessible);
+                        other_neighbors |= other.bfs(*color & accessible);
+                    }
+                    self_neighbors &= !self;
+                    other_neighbors &= !other;
+
+                    if self_neighbors.is_empty() && other_neighbors.is_empty() {
+                        return (self, other);
+                    }
+
+                    let self_captured = self_neighbors & !other_neighbors;
+                    let other_captured = other_neighbors & !self_neighbors;
+
+                    self |= self_captured;
+                    other |= other_captured;
+                    accessible &= !self_neighbors & !other_neighbors;
+                }
+            }
         }
-        sum as u32
-    }
 
-    pub fn get(self, row: usize, col: usize) -> bool {
-        debug_assert!(row < 16, "row out of bounds");
-        debug_assert!(col < 16, "col out of bounds");
+        impl core::fmt::Debug for Mask {
+            fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                f.debug_tuple("Mask")
+                    .field(&format_args!("{self:x}"))
+                    .finish()
+            }
+        }
+
+        /// One line per row, with `#` for cells in the mask and `.` for the others.
+        impl core::fmt::Display for Mask {
+            fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                for (row, bits) in <[u16; 16]>::from(*self).into_iter().enumerate() {
+                    if row > 0 {
+                        writeln!(f)?;
+                    }
+                    for col in 0..16 {
+                        f.write_str(if (bits >> col) & 1 == 1 { "#" } else { "." })?;
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        impl core::fmt::LowerHex for Mask {
+            fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                for row in <[u16; 16]>::from(*self) {
+                    write!(f, "{row:04x}")?;
+                }
+                Ok(())
+            }
+        }
+
+        /// Human readable formats get the hex string written by `{:x}`, others the 16 rows.
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for Mask {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                if serializer.is_human_readable() {
+                    serializer.collect_str(&format_args!("{self:x}"))
+                } else {
+                    <[u16; 16]>::from(*self).serialize(serializer)
+                }
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for Mask {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                if deserializer.is_human_readable() {
+                    let hex = String::deserialize(deserializer)?;
+                    Self::from_hex(&hex).ok_or_else(|| {
+                        serde::de::Error::invalid_value(
+                            serde::de::Unexpected::Str(&hex),
+                            &"64 hex digits",
+                        )
+                    })
+                } else {
+                    <[u16; 16]>::deserialize(deserializer).map(Self::from)
+                }
+            }
+        }
+    };
+}
+
+#[cfg(any(test, not(feature = "simd")))]
+mod scalar;
+#[cfg(feature = "simd")]
+mod simd;
 
-        (self.0.as_array()[row] >> col) & 1 == 1
+#[cfg(not(feature = "simd"))]
+pub use scalar::Mask;
+#[cfg(feature = "simd")]
+pub use simd::Mask;
+
+/// An iterator over the indices of the cells of a mask, from `Mask::indices`.
+#[derive(Clone, Debug)]
+pub struct Indices([u64; 4]);
+
+impl Iterator for Indices {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let (i, word) = self
+            .0
+            .iter_mut()
+            .enumerate()
+            .find(|(_, word)| **word != 0)?;
+        let bit = word.trailing_zeros() as usize;
+        *word &= *word - 1;
+        Some(64 * i + bit)
     }
 
-    pub fn flip_horizontal(self) -> Self {
-        Self(self.0.reverse_bits())
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.0.iter().map(|word| word.count_ones() as usize).sum();
+        (count, Some(count))
     }
+}
 
-    pub fn flip_vertical(self) -> Self {
-        Self(self.0.reverse())
+impl DoubleEndedIterator for Indices {
+    fn next_back(&mut self) -> Option<usize> {
+        let (i, word) = self
+            .0
+            .iter_mut()
+            .enumerate()
+            .rfind(|(_, word)| **word != 0)?;
+        let bit = 63 - word.leading_zeros() as usize;
+        *word &= !(1 << bit);
+        Some(64 * i + bit)
     }
+}
+
+impl ExactSizeIterator for Indices {}
 
-    pub fn flip(self) -> Self {
-        self.flip_horizontal().flip_vertical()
+impl core::iter::FusedIterator for Indices {}
+
+fn get_kth_one(mask: u64, mut k: u32) -> u64 {
+    let mut shift = 0;
+
+    macro_rules! iteration {
+        ($x:expr) => {{
+            #![allow(unused_assignments)]
+            let submask = (mask >> shift) & (1 << $x) - 1;
+            let count = submask.count_ones();
+            let in_high_bits = k >= count;
+            k -= count * u32::from(in_high_bits);
+            shift += $x * u64::from(in_high_bits);
+        }};
     }
 
-    pub fn sample(self, rng: &mut impl Rng) -> Self {
-        let array = u64x4::from_ne_bytes(self.0.to_ne_bytes()).to_array();
-        let [x0, x1, x2, x3] = array;
+    iteration!(32);
+    iteration!(16);
+    iteration!(8);
+    iteration!(4);
+    iteration!(2);
+    iteration!(1);
 
-        let count0 = x0.count_ones();
-        let count1 = x1.count_ones();
-        let count2 = x2.count_ones();
-        let count3 = x3.count_ones();
+    1 << shift
+}
 
-        let total = count0 + count1 + count2 + count3;
-        let mut k = rng.random_range(0..total);
+#[cfg(all(test, feature = "simd"))]
+mod tests {
+    use rand::prelude::*;
+    use rand_pcg::Pcg64;
 
-        let in_x2_or_x3 = k >= count0 + count1;
-        k -= (count0 + count1) * u32::from(in_x2_or_x3);
-        let count = if in_x2_or_x3 { count2 } else { count0 };
-        let in_x1_or_x3 = k >= count;
-        k -= count * u32::from(in_x1_or_x3);
+    use super::{scalar, simd};
 
-        let i = 2 * usize::from(in_x2_or_x3) + usize::from(in_x1_or_x3);
-        let mut out = [0u64; 4];
-        out[i] = get_kth_one(array[i], k);
-        Self(u16x16::from_ne_bytes(u64x4::from_array(out).to_le_bytes()))
-    }
+    const TRIALS: usize = 1000;
 
-    pub fn random(rng: &mut impl Rng) -> Self {
-        let mut array: [u16; 16] = [0; 16];
-        rng.fill(&mut array);
-        array.into()
+    fn rows(mask: impl Into<[u16; 16]>) -> [u16; 16] {
+        mask.into()
     }
 
-    pub fn bfs(mut self, accessible: Self) -> Self {
-        loop {
-            let captured = self.neighbors() & accessible;
-            if captured.is_empty() {
-                return self;
-            } else {
-                self |= captured;
+    /// Random rows with roughly one cell in `1 << sparsity` set.
+    fn random_rows(rng: &mut impl Rng, sparsity: u32) -> [u16; 16] {
+        let mut array: [u16; 16] = rng.random();
+        for _ in 0..sparsity {
+            let other: [u16; 16] = rng.random();
+            for (row, other) in array.iter_mut().zip(other) {
+                *row &= other;
             }
         }
+        array
+    }
+
+    fn pairs(
+        seed: u64,
+        sparsity: u32,
+    ) -> impl Iterator<Item = ([u16; 16], simd::Mask, scalar::Mask)> {
+        let mut rng = Pcg64::seed_from_u64(seed);
+        (0..TRIALS).map(move |_| {
+            let sparsity = rng.random_range(0..=sparsity);
+            let array = random_rows(&mut rng, sparsity);
+            (array, array.into(), array.into())
+        })
+    }
+
+    #[test]
+    fn conversions() {
+        for (array, simd, scalar) in pairs(0, 3) {
+            assert_eq!(rows(simd), array);
+            assert_eq!(rows(scalar), array);
+            assert_eq!(format!("{simd:x}"), format!("{scalar:x}"));
+            assert_eq!(
+                rows(scalar::Mask::from_hex(&format!("{simd:x}")).unwrap()),
+                array
+            );
+        }
+        assert_eq!(rows(simd::Mask::empty()), rows(scalar::Mask::empty()));
+        assert_eq!(rows(simd::Mask::full()), rows(scalar::Mask::full()));
     }
 
-    pub fn closer(mut self, mut other: Self, walls: Self) -> (Self, Self) {
-        let mut accessible = !(self | other | walls);
-        loop {
-            let self_neighbors = self.neighbors() & accessible & !other;
-            let other_neighbors = other.neighbors() & accessible & !self;
-
-            if self_neighbors.is_empty() && other_neighbors.is_empty() {
-                return (self, other);
+    #[test]
+    fn cells() {
+        for row in 0..16 {
+            for col in 0..16 {
+                let simd = simd::Mask::one_hot(row, col);
+                let scalar = scalar::Mask::one_hot(row, col);
+                assert_eq!(rows(simd), rows(scalar));
+            }
+        }
+        for (_, simd, scalar) in pairs(1, 3) {
+            for row in 0..16 {
+                for col in 0..16 {
+                    assert_eq!(simd.get(row, col), scalar.get(row, col));
+                }
             }
+            assert_eq!(simd.count_ones(), scalar.count_ones());
+            assert_eq!(simd.is_empty(), scalar.is_empty());
+            assert_eq!(simd.is_full(), scalar.is_full());
+        }
+    }
 
-            let self_captured = self_neighbors & !other_neighbors;
-            let other_captured = other_neighbors & !self_neighbors;
+    #[test]
+    fn iteration() {
+        for (array, simd, scalar) in pairs(8, 3) {
+            let naive: Vec<_> = (0..256)
+                .filter(|index| (array[index / 16] >> (index % 16)) & 1 == 1)
+                .collect();
+            assert_eq!(simd.indices().collect::<Vec<_>>(), naive);
+            assert_eq!(scalar.indices().collect::<Vec<_>>(), naive);
+            assert_eq!(simd.indices().len(), naive.len());
+            assert_eq!(
+                simd.indices().rev().collect::<Vec<_>>(),
+                naive.iter().rev().copied().collect::<Vec<_>>()
+            );
+            assert!(simd.cells().all(|(row, col)| simd.get(row, col)));
+            assert_eq!(rows(simd::Mask::from_cells(simd.cells())), array);
+            assert_eq!(
+                rows(scalar::Mask::from_indices(naive.iter().copied())),
+                array
+            );
+
+            assert_eq!(simd.lowest(), naive.first().copied());
+            let (mut simd_rest, mut scalar_rest) = (simd, scalar);
+            for &index in &naive {
+                assert_eq!(simd_rest.pop_lowest(), Some(index));
+                assert_eq!(scalar_rest.pop_lowest(), Some(index));
+            }
+            assert!(simd_rest.is_empty() && scalar_rest.is_empty());
+            assert_eq!(simd_rest.pop_lowest(), None);
+
+            let row_counts = array.map(u16::count_ones);
+            let col_counts: [u32; 16] = core::array::from_fn(|col| {
+                (0..16).filter(|&row| simd.get(row, col)).count() as u32
+            });
+            assert_eq!(simd.row_counts(), row_counts);
+            assert_eq!(scalar.row_counts(), row_counts);
+            assert_eq!(simd.col_counts(), col_counts);
+            assert_eq!(scalar.col_counts(), col_counts);
+        }
+        for index in 0..256 {
+            assert_eq!(
+                rows(simd::Mask::from_index(index)),
+                rows(simd::Mask::one_hot(index / 16, index % 16))
+            );
+            assert_eq!(
+                rows(scalar::Mask::from_index(index)),
+                rows(scalar::Mask::one_hot(index / 16, index % 16))
+            );
+        }
+    }
 
-            self |= self_captured;
-            other |= other_captured;
-            accessible &= !self_neighbors & !other_neighbors;
+    #[test]
+    fn shifts_and_flips() {
+        for (_, simd, scalar) in pairs(2, 3) {
+            assert_eq!(rows(simd.shift_left()), rows(scalar.shift_left()));
+            assert_eq!(rows(simd.shift_right()), rows(scalar.shift_right()));
+            assert_eq!(rows(simd.shift_up()), rows(scalar.shift_up()));
+            assert_eq!(rows(simd.shift_down()), rows(scalar.shift_down()));
+            assert_eq!(rows(simd.neighbors()), rows(scalar.neighbors()));
+            assert_eq!(rows(simd.neighbors2()), rows(scalar.neighbors2()));
+            assert_eq!(rows(simd.flip_horizontal()), rows(scalar.flip_horizontal()));
+            assert_eq!(rows(simd.flip_vertical()), rows(scalar.flip_vertical()));
+            assert_eq!(rows(simd.flip()), rows(scalar.flip()));
         }
     }
 
-    pub fn closer_by_color(
-        mut self,
-        mut other: Self,
-        walls: Self,
-        colors: &[Mask; 8],
-    ) -> (Self, Self) {
-        let mut accessible = !(self | other | walls);
-        loop {
-            let mut self_neighbors = Self::empty();
-            let mut other_neighbors = Self::empty();
-            for color in colors {
-                self_neighbors |= self.bfs(*color & accessible);
-                other_neighbors |= other.bfs(*color & accessible);
+    /// Moves every cell `(row, col)` of `array` to `f(row, col)`, dropping cells moved off the
+    /// board.
+    fn naive(array: [u16; 16], f: impl Fn(usize, usize) -> Option<(usize, usize)>) -> [u16; 16] {
+        let mut out = [0u16; 16];
+        for (row, bits) in array.into_iter().enumerate() {
+            for col in (0..16).filter(|col| (bits >> col) & 1 == 1) {
+                if let Some((row, col)) = f(row, col).filter(|&(row, col)| row < 16 && col < 16) {
+                    out[row] |= 1 << col;
+                }
             }
-            self_neighbors &= !self;
-            other_neighbors &= !other;
+        }
+        out
+    }
 
-            if self_neighbors.is_empty() && other_neighbors.is_empty() {
-                return (self, other);
-            }
+    #[test]
+    fn transposes_and_rotations() {
+        for (array, simd, scalar) in pairs(9, 3) {
+            let transposed = naive(array, |row, col| Some((col, row)));
+            assert_eq!(rows(simd.transpose()), transposed);
+            assert_eq!(rows(scalar.transpose()), transposed);
+
+            let rotated = naive(array, |row, col| Some((col, 15 - row)));
+            assert_eq!(rows(simd.rotate_90()), rotated);
+            assert_eq!(rows(scalar.rotate_90()), rotated);
 
-            let self_captured = self_neighbors & !other_neighbors;
-            let other_captured = other_neighbors & !self_neighbors;
+            let rotated = naive(array, |row, col| Some((15 - col, row)));
+            assert_eq!(rows(simd.rotate_270()), rotated);
+            assert_eq!(rows(scalar.rotate_270()), rotated);
+            assert_eq!(rows(simd.rotate_90().rotate_90()), rows(simd.flip()));
 
-            self |= self_captured;
-            other |= other_captured;
-            accessible &= !self_neighbors & !other_neighbors;
+            let shifted = naive(array, |row, col| Some((row + 1, col + 1)));
+            assert_eq!(rows(simd.shift_diagonal()), shifted);
+            assert_eq!(rows(scalar.shift_diagonal()), shifted);
         }
     }
-}
 
-impl BitAnd for Mask {
-    type Output = Self;
+    #[test]
+    fn bit_ops() {
+        for ((_, a, b), (_, c, d)) in pairs(3, 2).zip(pairs(4, 2)) {
+            assert_eq!(rows(a & c), rows(b & d));
+            assert_eq!(rows(a | c), rows(b | d));
+            assert_eq!(rows(a ^ c), rows(b ^ d));
+            assert_eq!(rows(!a), rows(!b));
+        }
+    }
 
-    fn bitand(self, rhs: Self) -> Self::Output {
-        Self(self.0 & rhs.0)
+    #[test]
+    fn searches() {
+        let mut rng = Pcg64::seed_from_u64(5);
+        for _ in 0..TRIALS {
+            let start = random_rows(&mut rng, 5);
+            let other = random_rows(&mut rng, 5);
+            let walls = random_rows(&mut rng, 2);
+            let colors: [[u16; 16]; 8] = core::array::from_fn(|_| random_rows(&mut rng, 2));
+
+            let simd_colors = colors.map(simd::Mask::from);
+            let scalar_colors = colors.map(scalar::Mask::from);
+            let (simd, scalar) = (simd::Mask::from(start), scalar::Mask::from(start));
+            let (simd_other, scalar_other) = (simd::Mask::from(other), scalar::Mask::from(other));
+            let (simd_walls, scalar_walls) = (simd::Mask::from(walls), scalar::Mask::from(walls));
+
+            assert_eq!(rows(simd.bfs(!simd_walls)), rows(scalar.bfs(!scalar_walls)));
+
+            let layers = simd.layers(!simd_walls);
+            assert_eq!(
+                layers.iter().copied().map(rows).collect::<Vec<_>>(),
+                scalar
+                    .layers(!scalar_walls)
+                    .into_iter()
+                    .map(rows)
+                    .collect::<Vec<_>>()
+            );
+            let reached = layers.iter().fold(simd, |seen, &ring| seen | ring);
+            assert_eq!(rows(reached), rows(simd.bfs(!simd_walls)));
+            assert_eq!(simd.distances(!simd_walls), scalar.distances(!scalar_walls));
+            assert_eq!(
+                simd.color_distances(!simd_walls, &simd_colors),
+                scalar.color_distances(!scalar_walls, &scalar_colors)
+            );
+
+            let (a, b) = simd.closer(simd_other, simd_walls);
+            let (c, d) = scalar.closer(scalar_other, scalar_walls);
+            assert_eq!((rows(a), rows(b)), (rows(c), rows(d)));
+
+            let (a, b) = simd.closer_by_color(simd_other, simd_walls, &simd_colors);
+            let (c, d) = scalar.closer_by_color(scalar_other, scalar_walls, &scalar_colors);
+            assert_eq!((rows(a), rows(b)), (rows(c), rows(d)));
+        }
     }
-}
 
-impl BitAndAssign for Mask {
-    fn bitand_assign(&mut self, rhs: Self) {
-        self.0 &= rhs.0
+    #[test]
+    fn sample_and_score() {
+        let mut simd_rng = Pcg64::seed_from_u64(6);
+        let scores: [[u8; 16]; 16] = simd_rng.random();
+        let mut scalar_rng = simd_rng.clone();
+        for (_, simd, scalar) in pairs(7, 4).filter(|(_, simd, _)| !simd.is_empty()) {
+            assert_eq!(
+                rows(simd.sample(&mut simd_rng)),
+                rows(scalar.sample(&mut scalar_rng))
+            );
+            assert_eq!(simd.score(&scores), scalar.score(&scores));
+        }
     }
 }
 
-impl BitXor for Mask {
-    type Output = Self;
+/// Checks whichever backend is enabled against a plain `[[bool; 16]; 16]` board.
+#[cfg(test)]
+mod reference_tests {
+    use rand::prelude::*;
+    use rand_pcg::Pcg64;
+
+    use super::Mask;
+
+    const TRIALS: usize = 1000;
+
+    type Board = [[bool; 16]; 16];
 
-    fn bitxor(self, rhs: Self) -> Self::Output {
-        Self(self.0 ^ rhs.0)
+    fn board(mask: Mask) -> Board {
+        core::array::from_fn(|row| core::array::from_fn(|col| mask.get(row, col)))
     }
-}
 
-impl BitXorAssign for Mask {
-    fn bitxor_assign(&mut self, rhs: Self) {
-        self.0 ^= rhs.0;
+    fn mask(board: &Board) -> Mask {
+        Mask::from_cells(
+            (0..16)
+                .flat_map(|row| (0..16).map(move |col| (row, col)))
+                .filter(|&(row, col)| board[row][col]),
+        )
     }
-}
 
-impl BitOr for Mask {
-    type Output = Self;
+    /// A random board with one cell in 1, 2, 4 or 8 set.
+    fn random_board(rng: &mut impl Rng) -> Board {
+        let sparsity = rng.random_range(0..4);
+        core::array::from_fn(|_| core::array::from_fn(|_| rng.random_ratio(1, 1 << sparsity)))
+    }
 
-    fn bitor(self, rhs: Self) -> Self::Output {
-        Self(self.0 | rhs.0)
+    fn grid(f: impl Fn(usize, usize) -> bool) -> Board {
+        core::array::from_fn(|row| core::array::from_fn(|col| f(row, col)))
     }
-}
 
-impl BitOrAssign for Mask {
-    fn bitor_assign(&mut self, rhs: Self) {
-        self.0 |= rhs.0;
+    /// `board[row + dr][col + dc]`, or false off the board.
+    fn at(board: &Board, row: usize, col: usize, dr: isize, dc: isize) -> bool {
+        let (Some(row), Some(col)) = (row.checked_add_signed(dr), col.checked_add_signed(dc))
+        else {
+            return false;
+        };
+        row < 16 && col < 16 && board[row][col]
     }
-}
 
-impl Not for Mask {
-    type Output = Self;
+    fn neighbors(board: &Board) -> Board {
+        grid(|row, col| {
+            !board[row][col]
+                && [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                    .into_iter()
+                    .any(|(dr, dc)| at(board, row, col, dr, dc))
+        })
+    }
 
-    fn not(self) -> Self::Output {
-        Self(!self.0)
+    /// The start plus every allowed cell joined to it by a path of allowed cells.
+    fn bfs(start: &Board, allowed: &Board) -> Board {
+        let mut reached = *start;
+        loop {
+            let next = neighbors(&reached);
+            let next = grid(|row, col| reached[row][col] || (allowed[row][col] && next[row][col]));
+            if next == reached {
+                return reached;
+            }
+            reached = next;
+        }
+    }
+
+    /// The number of steps from `start` to every cell, moving through `allowed` cells. The start
+    /// cells are at distance 0 whether allowed or not.
+    fn bfs_distances(start: &Board, allowed: &Board) -> [[Option<u8>; 16]; 16] {
+        let mut distances = start.map(|row| row.map(|cell| cell.then_some(0)));
+        let mut queue: std::collections::VecDeque<_> = (0..16)
+            .flat_map(|row| (0..16).map(move |col| (row, col)))
+            .filter(|&(row, col)| start[row][col])
+            .collect();
+        while let Some((row, col)) = queue.pop_front() {
+            let distance = distances[row][col].unwrap();
+            for (dr, dc) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let (Some(next_row), Some(next_col)) =
+                    (row.checked_add_signed(dr), col.checked_add_signed(dc))
+                else {
+                    continue;
+                };
+                if next_row < 16
+                    && next_col < 16
+                    && allowed[next_row][next_col]
+                    && distances[next_row][next_col].is_none()
+                {
+                    distances[next_row][next_col] = Some(distance + 1);
+                    queue.push_back((next_row, next_col));
+                }
+            }
+        }
+        distances
+    }
+
+    /// The number of color moves from `start` to every cell, where a move captures the allowed
+    /// cells of one color connected to what was captured before.
+    fn move_distances(
+        start: &Board,
+        allowed: &Board,
+        colors: &[Board; 8],
+    ) -> [[Option<u8>; 16]; 16] {
+        let mut distances = start.map(|row| row.map(|cell| cell.then_some(0)));
+        for moves in 1.. {
+            let seen = grid(|row, col| distances[row][col].is_some());
+            let mut captured = false;
+            for color in colors {
+                let reached = bfs(
+                    &seen,
+                    &grid(|row, col| allowed[row][col] && color[row][col]),
+                );
+                for (row, col) in (0..16).flat_map(|row| (0..16).map(move |col| (row, col))) {
+                    if reached[row][col] && distances[row][col].is_none() {
+                        distances[row][col] = Some(moves);
+                        captured = true;
+                    }
+                }
+            }
+            if !captured {
+                break;
+            }
+        }
+        distances
     }
-}
 
-impl From<[u16; 16]> for Mask {
-    fn from(array: [u16; 16]) -> Self {
-        Self(u16x16::from_array(array))
+    /// The cells at `distance` in a grid of distances.
+    fn ring(distances: &[[Option<u8>; 16]; 16], distance: usize) -> Board {
+        grid(|row, col| distances[row][col] == Some(distance as u8))
     }
-}
 
-impl From<Mask> for [u16; 16] {
-    fn from(mask: Mask) -> Self {
-        mask.0.to_array()
+    #[test]
+    fn cells_and_counts() {
+        let mut rng = Pcg64::seed_from_u64(0);
+        for _ in 0..TRIALS {
+            let a = random_board(&mut rng);
+            let count = a.iter().flatten().filter(|&&cell| cell).count();
+            assert_eq!(board(mask(&a)), a);
+            assert_eq!(mask(&a).count_ones() as usize, count);
+            assert_eq!(mask(&a).is_empty(), count == 0);
+            assert_eq!(mask(&a).is_full(), count == 256);
+        }
+        assert_eq!(board(Mask::empty()), [[false; 16]; 16]);
+        assert_eq!(board(Mask::full()), [[true; 16]; 16]);
+    }
+
+    #[test]
+    fn bit_ops() {
+        let mut rng = Pcg64::seed_from_u64(1);
+        for _ in 0..TRIALS {
+            let (a, b) = (random_board(&mut rng), random_board(&mut rng));
+            let (x, y) = (mask(&a), mask(&b));
+            assert_eq!(board(x & y), grid(|row, col| a[row][col] && b[row][col]));
+            assert_eq!(board(x | y), grid(|row, col| a[row][col] || b[row][col]));
+            assert_eq!(board(x ^ y), grid(|row, col| a[row][col] != b[row][col]));
+            assert_eq!(board(!x), grid(|row, col| !a[row][col]));
+        }
     }
-}
 
-fn get_kth_one(mask: u64, mut k: u32) -> u64 {
-    let mut shift = 0;
+    #[test]
+    fn shifts_and_flips() {
+        let mut rng = Pcg64::seed_from_u64(2);
+        for _ in 0..TRIALS {
+            let a = random_board(&mut rng);
+            let x = mask(&a);
+            assert_eq!(
+                board(x.shift_left()),
+                grid(|row, col| at(&a, row, col, 0, -1))
+            );
+            assert_eq!(
+                board(x.shift_right()),
+                grid(|row, col| at(&a, row, col, 0, 1))
+            );
+            assert_eq!(board(x.shift_up()), grid(|row, col| at(&a, row, col, 1, 0)));
+            assert_eq!(
+                board(x.shift_down()),
+                grid(|row, col| at(&a, row, col, -1, 0))
+            );
+            assert_eq!(board(x.neighbors()), neighbors(&a));
+            assert_eq!(
+                board(x.flip_horizontal()),
+                grid(|row, col| a[row][15 - col])
+            );
+            assert_eq!(board(x.flip_vertical()), grid(|row, col| a[15 - row][col]));
+            assert_eq!(board(x.flip()), grid(|row, col| a[15 - row][15 - col]));
+            assert_eq!(board(x.transpose()), grid(|row, col| a[col][row]));
+        }
+    }
 
-    macro_rules! iteration {
-        ($x:expr) => {{
-            #![allow(unused_assignments)]
-            let submask = (mask >> shift) & (1 << $x) - 1;
-            let count = submask.count_ones();
-            let in_high_bits = k >= count;
-            k -= count * u32::from(in_high_bits);
-            shift += $x * u64::from(in_high_bits);
-        }};
+    #[test]
+    fn bfs_and_sample() {
+        let mut rng = Pcg64::seed_from_u64(3);
+        for _ in 0..TRIALS {
+            let start = random_board(&mut rng);
+            let allowed = random_board(&mut rng);
+            let x = mask(&start);
+            assert_eq!(board(x.bfs(mask(&allowed))), bfs(&start, &allowed));
+
+            if !x.is_empty() {
+                let sample = x.sample(&mut rng);
+                assert_eq!(sample.count_ones(), 1);
+                assert_eq!(sample & x, sample);
+            }
+        }
     }
 
-    iteration!(32);
-    iteration!(16);
-    iteration!(8);
-    iteration!(4);
-    iteration!(2);
-    iteration!(1);
+    #[test]
+    fn display() {
+        let x = Mask::from_cells([(0, 0), (0, 3), (1, 15), (14, 8)]) | Mask::from_indices(240..256);
+        let mut expected = vec!["................"; 16];
+        expected[0] = "#..#............";
+        expected[1] = "...............#";
+        expected[14] = "........#.......";
+        expected[15] = "################";
+        assert_eq!(x.to_string(), expected.join("\n"));
+        assert_eq!(
+            Mask::empty().to_string(),
+            ["................"; 16].join("\n")
+        );
+    }
+
+    #[test]
+    fn distances_and_layers() {
+        let mut rng = Pcg64::seed_from_u64(4);
+        for _ in 0..TRIALS {
+            let start = random_board(&mut rng);
+            let allowed = random_board(&mut rng);
+            let x = mask(&start);
+
+            let expected = bfs_distances(&start, &allowed);
+            assert_eq!(x.distances(mask(&allowed)), expected);
+            let layers = x.layers(mask(&allowed));
+            for (distance, layer) in layers.iter().enumerate() {
+                assert_eq!(board(*layer), ring(&expected, distance + 1));
+            }
+            assert!(
+                ring(&expected, layers.len() + 1)
+                    .iter()
+                    .flatten()
+                    .all(|&cell| !cell)
+            );
+
+            let cells: [[usize; 16]; 16] =
+                core::array::from_fn(|_| core::array::from_fn(|_| rng.random_range(0..8)));
+            let colors: [Board; 8] =
+                core::array::from_fn(|color| grid(|row, col| cells[row][col] == color));
+            let color_masks = colors.map(|color| mask(&color));
+            let expected = move_distances(&start, &allowed, &colors);
+            assert_eq!(x.color_distances(mask(&allowed), &color_masks), expected);
+            let layers = x.color_layers(mask(&allowed), &color_masks);
+            for (distance, layer) in layers.iter().enumerate() {
+                assert_eq!(board(*layer), ring(&expected, distance + 1));
+            }
+            assert!(
+                ring(&expected, layers.len() + 1)
+                    .iter()
+                    .flatten()
+                    .all(|&cell| !cell)
+            );
+        }
+    }
 
-    1 << shift
+    #[test]
+    fn color_distances_by_hand() {
+        // Every column is one color, cycling through three, behind a wall in column 5 with a gap
+        // in the last row. Capturing column `col` takes `col` moves, except for the rest of the
+        // starting column, which is the start's color and only takes one.
+        let walls = grid(|row, col| col == 5 && row < 15);
+        let colors: [Board; 8] = core::array::from_fn(|color| grid(|_, col| col % 3 == color));
+        let distances =
+            Mask::one_hot(0, 0).color_distances(!mask(&walls), &colors.map(|color| mask(&color)));
+        for (row, col) in (0..16).flat_map(|row| (0..16).map(move |col| (row, col))) {
+            let expected = match (row, col) {
+                _ if walls[row][col] => None,
+                (0, 0) => Some(0),
+                (_, 0) => Some(1),
+                (_, col) => Some(col as u8),
+            };
+            assert_eq!(distances[row][col], expected, "({row}, {col})");
+        }
+    }
+
+    #[test]
+    fn components() {
+        let mut rng = Pcg64::seed_from_u64(5);
+        for _ in 0..TRIALS {
+            let a = random_board(&mut rng);
+            let mut union = Mask::empty();
+            let mut previous = None;
+            for component in mask(&a).components() {
+                assert!((union & component).is_empty());
+                union |= component;
+
+                let first = component.lowest().unwrap();
+                assert!(previous < Some(first));
+                previous = Some(first);
+                let start = board(Mask::from_index(first));
+                assert_eq!(bfs(&start, &a), board(component));
+            }
+            assert_eq!(board(union), a);
+        }
+    }
 }
@@ -0,0 +1,222 @@
+//! A `Mask` backed by plain integers, for toolchains without portable SIMD.
+//!
+//! Word `i` holds rows `4 * i` to `4 * i + 3`, row `r` in bits `16 * (r % 4)..16 * (r % 4 + 1)`.
+//! This is the same memory layout as the SIMD backend on little-endian targets.
+
+use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+
+use rand::Rng;
+
+use super::get_kth_one;
+
+/// The lowest bit of each row.
+const LOW: u64 = 0x0001_0001_0001_0001;
+/// The highest bit of each row.
+const HIGH: u64 = 0x8000_8000_8000_8000;
+
+/// A set of cells on the 16x16 board.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Mask([u64; 4]);
+
+/// Reverses the order of the 4 rows in a word.
+fn reverse_rows(word: u64) -> u64 {
+    let word = word.rotate_left(32);
+    ((word & 0x0000_ffff_0000_ffff) << 16) | ((word >> 16) & 0x0000_ffff_0000_ffff)
+}
+
+impl Mask {
+    pub const fn empty() -> Self {
+        Self([0; 4])
+    }
+
+    pub const fn full() -> Self {
+        Self([u64::MAX; 4])
+    }
+
+    /// Rows `4 * i` to `4 * i + 3` packed into word `i`, top row in the lowest bits.
+    pub(super) fn to_words(self) -> [u64; 4] {
+        self.0
+    }
+
+    pub(super) fn from_words(words: [u64; 4]) -> Self {
+        Self(words)
+    }
+
+    pub fn one_hot(row: usize, col: usize) -> Self {
+        debug_assert!(row < 16, "row out of bounds");
+        debug_assert!(col < 16, "col out of bounds");
+
+        let mut words = [0; 4];
+        words[row / 4] = 1 << (16 * (row % 4) + col);
+        Self(words)
+    }
+
+    /// Moves every cell one column right, towards higher bits.
+    pub fn shift_left(self) -> Self {
+        Self(self.0.map(|word| (word << 1) & !LOW))
+    }
+
+    /// Moves every cell one column left, towards lower bits.
+    pub fn shift_right(self) -> Self {
+        Self(self.0.map(|word| (word >> 1) & !HIGH))
+    }
+
+    /// Moves every cell one row up.
+    pub fn shift_up(self) -> Self {
+        let [w0, w1, w2, w3] = self.0;
+        Self([
+            (w0 >> 16) | (w1 << 48),
+            (w1 >> 16) | (w2 << 48),
+            (w2 >> 16) | (w3 << 48),
+            w3 >> 16,
+        ])
+    }
+
+    /// Moves every cell one row down.
+    pub fn shift_down(self) -> Self {
+        let [w0, w1, w2, w3] = self.0;
+        Self([
+            w0 << 16,
+            (w1 << 16) | (w0 >> 48),
+            (w2 << 16) | (w1 >> 48),
+            (w3 << 16) | (w2 >> 48),
+        ])
+    }
+
+    pub fn count_ones(self) -> u32 {
+        self.0.iter().map(|word| word.count_ones()).sum()
+    }
+
+    /// The number of cells in each row.
+    pub fn row_counts(self) -> [u32; 16] {
+        <[u16; 16]>::from(self).map(u16::count_ones)
+    }
+
+    /// The sum of `scores` over the cells in the mask.
+    pub fn score(self, scores: &[[u8; 16]; 16]) -> u32 {
+        let mut sum = 0;
+        for (row, mut bits) in <[u16; 16]>::from(self).into_iter().enumerate() {
+            while bits != 0 {
+                sum += u32::from(scores[row][bits.trailing_zeros() as usize]);
+                bits &= bits - 1;
+            }
+        }
+        sum
+    }
+
+    pub fn get(self, row: usize, col: usize) -> bool {
+        debug_assert!(row < 16, "row out of bounds");
+        debug_assert!(col < 16, "col out of bounds");
+
+        (self.0[row / 4] >> (16 * (row % 4) + col)) & 1 == 1
+    }
+
+    pub fn flip_horizontal(self) -> Self {
+        Self(self.0.map(|word| reverse_rows(word.reverse_bits())))
+    }
+
+    pub fn flip_vertical(self) -> Self {
+        let [w0, w1, w2, w3] = self.0;
+        Self([w3, w2, w1, w0].map(reverse_rows))
+    }
+
+    /// Mirrors the board along the main diagonal, so that rows become columns.
+    pub fn transpose(self) -> Self {
+        let mut rows = <[u16; 16]>::from(self);
+        for (k, mask) in [(8, 0x00ff), (4, 0x0f0f), (2, 0x3333), (1, 0x5555)] {
+            for top in (0..16).filter(|row| row & k == 0) {
+                let swapped = ((rows[top] >> k) ^ rows[top + k]) & mask;
+                rows[top] ^= swapped << k;
+                rows[top + k] ^= swapped;
+            }
+        }
+        rows.into()
+    }
+
+    /// A uniformly random cell of a non-empty mask.
+    pub fn sample(self, rng: &mut impl Rng) -> Self {
+        let [count0, count1, count2, count3] = self.0.map(u64::count_ones);
+
+        let total = count0 + count1 + count2 + count3;
+        let mut k = rng.random_range(0..total);
+
+        let in_x2_or_x3 = k >= count0 + count1;
+        k -= (count0 + count1) * u32::from(in_x2_or_x3);
+        let count = if in_x2_or_x3 { count2 } else { count0 };
+        let in_x1_or_x3 = k >= count;
+        k -= count * u32::from(in_x1_or_x3);
+
+        let i = 2 * usize::from(in_x2_or_x3) + usize::from(in_x1_or_x3);
+        let mut out = [0u64; 4];
+        out[i] = get_kth_one(self.0[i], k);
+        Self(out)
+    }
+}
+
+impl BitAnd for Mask {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self([0, 1, 2, 3].map(|i| self.0[i] & rhs.0[i]))
+    }
+}
+
+impl BitAndAssign for Mask {
+    fn bitand_assign(&mut self, rhs: Self) {
+        *self = *self & rhs;
+    }
+}
+
+impl BitXor for Mask {
+    type Output = Self;
+
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        Self([0, 1, 2, 3].map(|i| self.0[i] ^ rhs.0[i]))
+    }
+}
+
+impl BitXorAssign for Mask {
+    fn bitxor_assign(&mut self, rhs: Self) {
+        *self = *self ^ rhs;
+    }
+}
+
+impl BitOr for Mask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self([0, 1, 2, 3].map(|i| self.0[i] | rhs.0[i]))
+    }
+}
+
+impl BitOrAssign for Mask {
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = *self | rhs;
+    }
+}
+
+impl Not for Mask {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        Self(self.0.map(|word| !word))
+    }
+}
+
+impl From<[u16; 16]> for Mask {
+    fn from(array: [u16; 16]) -> Self {
+        let mut words = [0; 4];
+        for (row, bits) in array.into_iter().enumerate() {
+            words[row / 4] |= u64::from(bits) << (16 * (row % 4));
+        }
+        Self(words)
+    }
+}
+
+impl From<Mask> for [u16; 16] {
+    fn from(mask: Mask) -> Self {
+        core::array::from_fn(|row| (mask.0[row / 4] >> (16 * (row % 4))) as u16)
+    }
+}
+
+shared!();
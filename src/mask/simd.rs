@@ -0,0 +1,225 @@
+use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+use core::simd::{self, ToBytes, prelude::*};
+
+use rand::Rng;
+
+use super::get_kth_one;
+
+/// A set of cells on the 16x16 board, one `u16` per row with column `c` in bit `c`.
+#[repr(transparent)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Mask(u16x16);
+
+impl Mask {
+    pub const fn empty() -> Self {
+        Self(u16x16::splat(0))
+    }
+
+    pub const fn full() -> Self {
+        Self(u16x16::splat(u16::MAX))
+    }
+
+    /// Rows `4 * i` to `4 * i + 3` packed into word `i`, top row in the lowest bits.
+    pub(super) fn to_words(self) -> [u64; 4] {
+        u64x4::from_le_bytes(self.0.to_le_bytes()).to_array()
+    }
+
+    pub(super) fn from_words(words: [u64; 4]) -> Self {
+        Self(u16x16::from_le_bytes(
+            u64x4::from_array(words).to_le_bytes(),
+        ))
+    }
+
+    pub fn one_hot(row: usize, col: usize) -> Self {
+        debug_assert!(row < 16, "row out of bounds");
+        debug_assert!(col < 16, "col out of bounds");
+
+        let mut array = [0u16; 16];
+        array[row] = 1 << col;
+        Self(u16x16::from_array(array))
+    }
+
+    /// Moves every cell one column right, towards higher bits.
+    pub fn shift_left(self) -> Self {
+        Self(self.0 << 1)
+    }
+
+    /// Moves every cell one column left, towards lower bits.
+    pub fn shift_right(self) -> Self {
+        Self(self.0 >> 1)
+    }
+
+    /// Moves every cell one row up.
+    pub fn shift_up(self) -> Self {
+        Self(self.0.shift_elements_left::<1>(0))
+    }
+
+    /// Moves every cell one row down.
+    pub fn shift_down(self) -> Self {
+        Self(self.0.shift_elements_right::<1>(0))
+    }
+
+    pub fn count_ones(self) -> u32 {
+        u32::from(self.0.count_ones().reduce_sum())
+    }
+
+    /// The number of cells in each row.
+    pub fn row_counts(self) -> [u32; 16] {
+        self.0.count_ones().cast::<u32>().to_array()
+    }
+
+    /// The sum of `scores` over the cells in the mask.
+    pub fn score(self, scores: &[[u8; 16]; 16]) -> u32 {
+        let mut sum = 0;
+        let array = self.0.as_array();
+        for i in 0..16 {
+            let row = u8x16::from_array(scores[i]);
+            let mask = simd::Mask::<i8, 16>::from_bitmask(u64::from(array[i]));
+            let select = mask.select(row, u8x16::splat(0));
+            sum += select.cast::<u16>().reduce_sum();
+        }
+        u32::from(sum)
+    }
+
+    pub fn get(self, row: usize, col: usize) -> bool {
+        debug_assert!(row < 16, "row out of bounds");
+        debug_assert!(col < 16, "col out of bounds");
+
+        (self.0.as_array()[row] >> col) & 1 == 1
+    }
+
+    pub fn flip_horizontal(self) -> Self {
+        Self(self.0.reverse_bits())
+    }
+
+    pub fn flip_vertical(self) -> Self {
+        Self(self.0.reverse())
+    }
+
+    /// Mirrors the board along the main diagonal, so that rows become columns.
+    pub fn transpose(self) -> Self {
+        /// Swaps the top right and bottom left `k` by `k` block of each `2k` by `2k` block,
+        /// `mask` holding the low `k` columns of each block.
+        fn swap_blocks<const K: u16>(rows: u16x16, partners: u16x16, mask: u16) -> u16x16 {
+            let mask = u16x16::splat(mask);
+            let low = usize::from(K);
+            let is_top =
+                simd::Mask::<i16, 16>::from_array(core::array::from_fn(|row| row & low == 0));
+            let top = ((rows >> K) ^ partners) & mask;
+            let bottom = ((partners >> K) ^ rows) & mask;
+            rows ^ is_top.select(top << K, bottom)
+        }
+
+        let mut rows = self.0;
+        rows = swap_blocks::<8>(
+            rows,
+            simd_swizzle!(rows, [8, 9, 10, 11, 12, 13, 14, 15, 0, 1, 2, 3, 4, 5, 6, 7]),
+            0x00ff,
+        );
+        rows = swap_blocks::<4>(
+            rows,
+            simd_swizzle!(rows, [4, 5, 6, 7, 0, 1, 2, 3, 12, 13, 14, 15, 8, 9, 10, 11]),
+            0x0f0f,
+        );
+        rows = swap_blocks::<2>(
+            rows,
+            simd_swizzle!(rows, [2, 3, 0, 1, 6, 7, 4, 5, 10, 11, 8, 9, 14, 15, 12, 13]),
+            0x3333,
+        );
+        rows = swap_blocks::<1>(
+            rows,
+            simd_swizzle!(rows, [1, 0, 3, 2, 5, 4, 7, 6, 9, 8, 11, 10, 13, 12, 15, 14]),
+            0x5555,
+        );
+        Self(rows)
+    }
+
+    /// A uniformly random cell of a non-empty mask.
+    pub fn sample(self, rng: &mut impl Rng) -> Self {
+        let array = u64x4::from_ne_bytes(self.0.to_ne_bytes()).to_array();
+        let [x0, x1, x2, x3] = array;
+
+        let count0 = x0.count_ones();
+        let count1 = x1.count_ones();
+        let count2 = x2.count_ones();
+        let count3 = x3.count_ones();
+
+        let total = count0 + count1 + count2 + count3;
+        let mut k = rng.random_range(0..total);
+
+        let in_x2_or_x3 = k >= count0 + count1;
+        k -= (count0 + count1) * u32::from(in_x2_or_x3);
+        let count = if in_x2_or_x3 { count2 } else { count0 };
+        let in_x1_or_x3 = k >= count;
+        k -= count * u32::from(in_x1_or_x3);
+
+        let i = 2 * usize::from(in_x2_or_x3) + usize::from(in_x1_or_x3);
+        let mut out = [0u64; 4];
+        out[i] = get_kth_one(array[i], k);
+        Self(u16x16::from_ne_bytes(u64x4::from_array(out).to_le_bytes()))
+    }
+}
+
+impl BitAnd for Mask {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl BitAndAssign for Mask {
+    fn bitand_assign(&mut self, rhs: Self) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl BitXor for Mask {
+    type Output = Self;
+
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        Self(self.0 ^ rhs.0)
+    }
+}
+
+impl BitXorAssign for Mask {
+    fn bitxor_assign(&mut self, rhs: Self) {
+        self.0 ^= rhs.0;
+    }
+}
+
+impl BitOr for Mask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Mask {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl Not for Mask {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        Self(!self.0)
+    }
+}
+
+impl From<[u16; 16]> for Mask {
+    fn from(array: [u16; 16]) -> Self {
+        Self(u16x16::from_array(array))
+    }
+}
+
+impl From<Mask> for [u16; 16] {
+    fn from(mask: Mask) -> Self {
+        mask.0.to_array()
+    }
+}
+
+shared!();
@@ -0,0 +1,405 @@
+//! A small neural network evaluation.
+//!
+//! The network has a single hidden layer of clipped rectifiers over `INPUTS` binary inputs: 11 planes
+//! of 256 cells (player 1's territory, player 2's territory, walls, then the unclaimed cells of
+//! each color in `Color::LIST` order) followed by one input that is set when player 1 is next.
+//! Every cell belongs to exactly one plane, so the hidden layer is the sum of 256 weight columns
+//! plus the biases. Its output is the predicted final margin for player 1, in cells.
+//!
+//! Weights files are little-endian: the magic bytes `FLNN`, a `u32` format version (1), the
+//! hidden layer size as a `u32` (a multiple of 8), then `f32`s for the input weights
+//! (`INPUTS` rows of `hidden` weights), the hidden biases (`hidden`), the output weights
+//! (`hidden`) and the output bias (1).
+
+use core::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::bot::{Eval, Weighted};
+use crate::game::State;
+use crate::mask::Mask;
+
+use lanes::{Floats, Ints};
+
+pub const PLANES: usize = 11;
+pub const INPUTS: usize = PLANES * 256 + 1;
+
+const MAGIC: [u8; 4] = *b"FLNN";
+const VERSION: u32 = 1;
+const LANES: usize = 8;
+
+/// Fixed point scale of the hidden layer. Integer accumulators keep incremental updates exact.
+const QUANT: f32 = 1024.0;
+
+const PLAYER1: u8 = 0;
+const PLAYER2: u8 = 1;
+const WALLS: u8 = 2;
+const TEMPO: usize = PLANES * 256;
+
+pub struct Network {
+    input_weights: Vec<Ints>,
+    hidden_bias: Vec<Ints>,
+    output_weights: Vec<Floats>,
+    output_bias: f32,
+}
+
+impl Network {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        if bytes.len() < 12 || bytes[..4] != MAGIC {
+            return Err(invalid("not a network file"));
+        }
+        if bytes[4..8] != VERSION.to_le_bytes() {
+            return Err(invalid("unsupported network version"));
+        }
+        let hidden = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        if hidden == 0 || !hidden.is_multiple_of(LANES) {
+            return Err(invalid(
+                "hidden layer size must be a positive multiple of 8",
+            ));
+        }
+
+        let floats: Vec<f32> = bytes[12..]
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        if !bytes.len().is_multiple_of(4) || floats.len() != (INPUTS + 2) * hidden + 1 {
+            return Err(invalid("network file has the wrong size"));
+        }
+
+        let (input_weights, rest) = floats.split_at(INPUTS * hidden);
+        let (hidden_bias, rest) = rest.split_at(hidden);
+        let (output_weights, output_bias) = rest.split_at(hidden);
+
+        let quantize = |weights: &[f32]| -> Vec<Ints> {
+            weights
+                .chunks_exact(LANES)
+                .map(|chunk| lanes::quantize(chunk, QUANT))
+                .collect()
+        };
+        Ok(Self {
+            input_weights: quantize(input_weights),
+            hidden_bias: quantize(hidden_bias),
+            output_weights: output_weights
+                .chunks_exact(LANES)
+                .map(lanes::floats)
+                .collect(),
+            output_bias: output_bias[0],
+        })
+    }
+
+    fn column(&self, input: usize) -> &[Ints] {
+        let lanes = self.hidden_bias.len();
+        &self.input_weights[input * lanes..(input + 1) * lanes]
+    }
+
+    fn add(&self, accumulator: &mut [Ints], input: usize) {
+        for (value, weight) in accumulator.iter_mut().zip(self.column(input)) {
+            *value = lanes::add(*value, *weight);
+        }
+    }
+
+    fn sub(&self, accumulator: &mut [Ints], input: usize) {
+        for (value, weight) in accumulator.iter_mut().zip(self.column(input)) {
+            *value = lanes::sub(*value, *weight);
+        }
+    }
+
+    fn output(&self, accumulator: &[Ints], player1_next: bool) -> f32 {
+        let mut sum = lanes::zero();
+        let tempo = self.column(TEMPO);
+        for ((value, tempo), weight) in accumulator.iter().zip(tempo).zip(&self.output_weights) {
+            let value = if player1_next {
+                lanes::add(*value, *tempo)
+            } else {
+                *value
+            };
+            sum = lanes::mul_add(sum, lanes::clamp(value, QUANT as i32), *weight);
+        }
+        lanes::sum(sum) / QUANT + self.output_bias
+    }
+}
+
+/// The hidden layer for the last evaluated position, updated cell by cell when the next position
+/// is on the same board.
+struct Accumulator {
+    colors: [Mask; 8],
+    walls: Mask,
+    planes: [u8; 256],
+    player1: Mask,
+    player2: Mask,
+    values: Vec<Ints>,
+}
+
+impl Accumulator {
+    /// Above this many changed cells, recomputing from scratch is cheaper.
+    const REFRESH: u32 = 96;
+
+    fn new(network: &Network, state: &State) -> Self {
+        let mut planes = [WALLS; 256];
+        for (i, color) in state.colors.iter().enumerate() {
+            for cell in color.indices() {
+                planes[cell] = 3 + i as u8;
+            }
+        }
+
+        let mut values = network.hidden_bias.clone();
+        for cell in 0..256 {
+            let input = input(&planes, state.player1, state.player2, cell);
+            network.add(&mut values, input);
+        }
+
+        Self {
+            colors: state.colors,
+            walls: state.walls,
+            planes,
+            player1: state.player1,
+            player2: state.player2,
+            values,
+        }
+    }
+
+    fn same_board(&self, state: &State) -> bool {
+        self.walls == state.walls && self.colors == state.colors
+    }
+
+    fn update(&mut self, network: &Network, state: &State) {
+        let changed = (self.player1 ^ state.player1) | (self.player2 ^ state.player2);
+        if changed.count_ones() > Self::REFRESH {
+            *self = Self::new(network, state);
+            return;
+        }
+
+        for cell in changed.indices() {
+            network.sub(
+                &mut self.values,
+                input(&self.planes, self.player1, self.player2, cell),
+            );
+            network.add(
+                &mut self.values,
+                input(&self.planes, state.player1, state.player2, cell),
+            );
+        }
+        self.player1 = state.player1;
+        self.player2 = state.player2;
+    }
+}
+
+fn input(planes: &[u8; 256], player1: Mask, player2: Mask, cell: usize) -> usize {
+    let (row, col) = (cell / 16, cell % 16);
+    let plane = if player1.get(row, col) {
+        PLAYER1
+    } else if player2.get(row, col) {
+        PLAYER2
+    } else {
+        planes[cell]
+    };
+    usize::from(plane) * 256 + cell
+}
+
+/// Evaluates positions with a `Network`, reusing the hidden layer of the previous evaluation.
+/// It is not symmetric: the inputs tell the players apart and include the tempo.
+pub struct Neural {
+    network: Arc<Network>,
+    accumulator: RefCell<Option<Accumulator>>,
+}
+
+impl Neural {
+    pub fn new(network: Arc<Network>) -> Self {
+        Self {
+            network,
+            accumulator: RefCell::new(None),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(Arc::new(Network::load(path)?)))
+    }
+
+    pub fn predict(&self, state: &State) -> f32 {
+        let mut accumulator = self.accumulator.borrow_mut();
+        match accumulator.as_mut() {
+            Some(accumulator) if accumulator.same_board(state) => {
+                accumulator.update(&self.network, state);
+            }
+            _ => *accumulator = Some(Accumulator::new(&self.network, state)),
+        }
+        let values = &accumulator.as_ref().unwrap().values;
+        self.network.output(values, state.player1_next())
+    }
+}
+
+impl Clone for Neural {
+    fn clone(&self) -> Self {
+        Self::new(self.network.clone())
+    }
+}
+
+impl Eval for Neural {
+    fn eval(&self, state: &State) -> i32 {
+        (Weighted::SCALE as f32 * self.predict(state)).round() as i32
+    }
+
+    fn cell_value(&self) -> Option<i32> {
+        Some(Weighted::SCALE as i32)
+    }
+}
+
+/// Groups of `LANES` hidden layer values, with portable SIMD when it is available.
+#[cfg(feature = "simd")]
+mod lanes {
+    use core::simd::prelude::*;
+    use std::simd::StdFloat;
+
+    pub type Ints = i32x8;
+    pub type Floats = f32x8;
+
+    pub fn quantize(weights: &[f32], scale: f32) -> Ints {
+        (Floats::from_slice(weights) * Floats::splat(scale))
+            .round()
+            .cast()
+    }
+
+    pub fn floats(weights: &[f32]) -> Floats {
+        Floats::from_slice(weights)
+    }
+
+    pub fn zero() -> Floats {
+        Floats::splat(0.0)
+    }
+
+    pub fn add(a: Ints, b: Ints) -> Ints {
+        a + b
+    }
+
+    pub fn sub(a: Ints, b: Ints) -> Ints {
+        a - b
+    }
+
+    /// Clamps to `0..=max` and converts to floats.
+    pub fn clamp(a: Ints, max: i32) -> Floats {
+        a.simd_clamp(Ints::splat(0), Ints::splat(max)).cast()
+    }
+
+    pub fn mul_add(sum: Floats, a: Floats, b: Floats) -> Floats {
+        sum + a * b
+    }
+
+    pub fn sum(a: Floats) -> f32 {
+        a.reduce_sum()
+    }
+}
+
+#[cfg(not(feature = "simd"))]
+mod lanes {
+    use super::LANES;
+
+    pub type Ints = [i32; LANES];
+    pub type Floats = [f32; LANES];
+
+    pub fn quantize(weights: &[f32], scale: f32) -> Ints {
+        core::array::from_fn(|i| (weights[i] * scale).round() as i32)
+    }
+
+    pub fn floats(weights: &[f32]) -> Floats {
+        weights.try_into().unwrap()
+    }
+
+    pub fn zero() -> Floats {
+        [0.0; LANES]
+    }
+
+    pub fn add(a: Ints, b: Ints) -> Ints {
+        core::array::from_fn(|i| a[i] + b[i])
+    }
+
+    pub fn sub(a: Ints, b: Ints) -> Ints {
+        core::array::from_fn(|i| a[i] - b[i])
+    }
+
+    /// Clamps to `0..=max` and converts to floats.
+    pub fn clamp(a: Ints, max: i32) -> Floats {
+        a.map(|value| value.clamp(0, max) as f32)
+    }
+
+    pub fn mul_add(sum: Floats, a: Floats, b: Floats) -> Floats {
+        core::array::from_fn(|i| sum[i] + a[i] * b[i])
+    }
+
+    pub fn sum(a: Floats) -> f32 {
+        a.iter().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_pcg::Pcg64;
+
+    use super::*;
+
+    /// A weights file for a network with `hidden` random weights per input.
+    fn random_bytes(rng: &mut impl Rng, hidden: usize) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+        bytes.extend((hidden as u32).to_le_bytes());
+        for _ in 0..=(INPUTS + 2) * hidden {
+            bytes.extend(rng.random_range(-0.1f32..0.1).to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn incremental_matches_fresh() {
+        let mut rng = Pcg64::seed_from_u64(0);
+        let network = Arc::new(Network::from_bytes(&random_bytes(&mut rng, 16)).unwrap());
+        let neural = Neural::new(network.clone());
+        for seed in 0..10 {
+            let mut state = State::generate(seed);
+            while !state.game_over() {
+                let checkpoint = state.checkpoint();
+                for color in state.valid_moves() {
+                    state.play(color);
+                    let fresh = Neural::new(network.clone()).predict(&state);
+                    assert_eq!(neural.predict(&state).to_bits(), fresh.to_bits());
+                    state.restore(checkpoint);
+                    let fresh = Neural::new(network.clone()).predict(&state);
+                    assert_eq!(neural.predict(&state).to_bits(), fresh.to_bits());
+                }
+                state.play(state.valid_moves().choose(&mut rng).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        let mut rng = Pcg64::seed_from_u64(1);
+        let bytes = random_bytes(&mut rng, 8);
+        assert!(Network::from_bytes(&bytes).is_ok());
+
+        let mut magic = bytes.clone();
+        magic[0] = b'X';
+        assert!(Network::from_bytes(&magic).is_err());
+
+        let mut version = bytes.clone();
+        version[4] = 2;
+        assert!(Network::from_bytes(&version).is_err());
+
+        assert!(Network::from_bytes(&bytes[..bytes.len() - 4]).is_err());
+        assert!(Network::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Network::from_bytes(&[bytes.as_slice(), &[0; 4]].concat()).is_err());
+        assert!(Network::from_bytes(&bytes[..8]).is_err());
+
+        let mut hidden = random_bytes(&mut rng, 12);
+        assert!(Network::from_bytes(&hidden).is_err());
+        hidden[8..12].copy_from_slice(&0u32.to_le_bytes());
+        assert!(Network::from_bytes(&hidden[..12]).is_err());
+    }
+}
@@ -0,0 +1,291 @@
+//! The board as a graph of regions: maximal 4-connected groups of cells of one color.
+//!
+//! A move always captures whole regions, so positions can be described by sets of regions instead
+//! of sets of cells. Sets of regions are `Mask`s of region indices, which fits since there are at
+//! most 254 regions.
+
+use std::sync::Arc;
+
+use crate::game::{self, Color, Position, State};
+use crate::mask::Mask;
+
+/// A maximal 4-connected group of cells of one color.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub color: Color,
+    pub cells: Mask,
+    /// The regions sharing an edge with this one.
+    pub neighbors: Mask,
+}
+
+#[derive(Clone, Debug)]
+pub struct RegionGraph {
+    /// The regions, ordered by color, then by their first cell.
+    pub regions: Vec<Region>,
+    /// The region of each cell, or `None` for walls and the starting corners.
+    pub labels: [Option<u8>; 256],
+    /// The regions of each color.
+    pub by_color: [Mask; 8],
+}
+
+impl RegionGraph {
+    pub fn new(colors: &[Mask; 8]) -> Self {
+        let mut regions = Vec::new();
+        let mut labels = [None; 256];
+        let mut by_color = [Mask::empty(); 8];
+
+        for (color, mask) in Color::LIST.into_iter().zip(colors) {
+            for cells in mask.components() {
+                let index = regions.len();
+                for cell in cells.indices() {
+                    labels[cell] = Some(index as u8);
+                }
+                by_color[color as usize] |= Mask::from_index(index);
+                regions.push(Region {
+                    color,
+                    cells,
+                    neighbors: Mask::empty(),
+                });
+            }
+        }
+
+        let mut graph = Self {
+            regions,
+            labels,
+            by_color,
+        };
+        for index in 0..graph.regions.len() {
+            graph.regions[index].neighbors = graph.touching(graph.regions[index].cells);
+        }
+        graph
+    }
+
+    /// The regions containing any of `cells`.
+    pub fn containing(&self, cells: Mask) -> Mask {
+        Mask::from_indices(
+            cells
+                .indices()
+                .filter_map(|cell| self.labels[cell].map(usize::from)),
+        )
+    }
+
+    /// The regions next to `cells`, not containing any of them.
+    pub fn touching(&self, cells: Mask) -> Mask {
+        self.containing(cells.neighbors())
+    }
+
+    /// The regions next to any of `regions`, not in it.
+    pub fn neighbors(&self, regions: Mask) -> Mask {
+        let mut neighbors = Mask::empty();
+        for region in regions.indices() {
+            neighbors |= self.regions[region].neighbors;
+        }
+        neighbors & !regions
+    }
+
+    /// The cells of `regions`.
+    pub fn cells(&self, regions: Mask) -> Mask {
+        let mut cells = Mask::empty();
+        for region in regions.indices() {
+            cells |= self.regions[region].cells;
+        }
+        cells
+    }
+}
+
+/// A `State` that plays moves by merging regions instead of flooding cells.
+///
+/// The territories are kept as sets of regions, together with the regions next to them. A move
+/// captures the regions of its color next to the mover's territory that the opponent does not
+/// own, and the cells of the `State` are updated from those.
+#[derive(Clone, Debug)]
+pub struct RegionState {
+    graph: Arc<RegionGraph>,
+    state: State,
+    /// The regions owned by player 1 and player 2.
+    territories: [Mask; 2],
+    /// The regions next to each territory, not in it.
+    frontiers: [Mask; 2],
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Checkpoint {
+    state: game::Checkpoint,
+    territories: [Mask; 2],
+    frontiers: [Mask; 2],
+}
+
+impl RegionState {
+    pub fn new(state: State) -> Self {
+        Self::with_graph(Arc::new(RegionGraph::new(&state.colors)), state)
+    }
+
+    /// Like `new`, but reuses the graph of another position of the same board.
+    pub fn with_graph(graph: Arc<RegionGraph>, state: State) -> Self {
+        let territories = [
+            graph.containing(state.player1),
+            graph.containing(state.player2),
+        ];
+        let frontiers = [graph.touching(state.player1), graph.touching(state.player2)];
+        Self {
+            graph,
+            state,
+            territories,
+            frontiers,
+        }
+    }
+
+    pub fn graph(&self) -> &Arc<RegionGraph> {
+        &self.graph
+    }
+}
+
+impl Position for RegionState {
+    type Checkpoint = Checkpoint;
+
+    fn state(&self) -> &State {
+        &self.state
+    }
+
+    fn play(&mut self, color: Color) {
+        let (mover, opponent) = if self.state.player1_next() {
+            (0, 1)
+        } else {
+            (1, 0)
+        };
+        let captured = self.frontiers[mover]
+            & self.graph.by_color[color as usize]
+            & !self.territories[opponent];
+        self.territories[mover] |= captured;
+        self.frontiers[mover] =
+            (self.frontiers[mover] | self.graph.neighbors(captured)) & !self.territories[mover];
+
+        let cells = self.graph.cells(captured);
+        if mover == 0 {
+            self.state.player1 |= cells;
+            self.state.player1_last_move = Some(color);
+        } else {
+            self.state.player2 |= cells;
+            self.state.player2_last_move = Some(color);
+        }
+        self.state.round += 1;
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            state: self.state.checkpoint(),
+            territories: self.territories,
+            frontiers: self.frontiers,
+        }
+    }
+
+    fn restore(&mut self, checkpoint: Checkpoint) {
+        self.state.restore(checkpoint.state);
+        self.territories = checkpoint.territories;
+        self.frontiers = checkpoint.frontiers;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_pcg::Pcg64;
+
+    use super::*;
+    use crate::game::perft;
+
+    #[test]
+    fn same_perft_as_state() {
+        for seed in 0..4 {
+            let mut state = State::generate(seed);
+            for color in [Color::Red, Color::Green, Color::Blue, Color::Pink] {
+                state.try_play(color).unwrap();
+            }
+            let mut regions = RegionState::new(state);
+            assert_eq!(perft(&mut regions, 4), perft(&mut state, 4));
+        }
+    }
+
+    #[test]
+    fn same_as_state() {
+        let mut rng = Pcg64::seed_from_u64(0);
+        for seed in 0..100 {
+            let mut state = State::generate(seed);
+            let mut regions = RegionState::new(state);
+            let mut checkpoints = Vec::new();
+
+            while !state.game_over() {
+                checkpoints.push((state.checkpoint(), Position::checkpoint(&regions)));
+                let color = state.valid_moves().choose(&mut rng).unwrap();
+                state.play(color);
+                Position::play(&mut regions, color);
+
+                assert_eq!(regions.state.player1, state.player1);
+                assert_eq!(regions.state.player2, state.player2);
+                assert_eq!(regions.state.round, state.round);
+                assert_eq!(regions.state.game_over(), state.game_over());
+                let fresh = RegionState::with_graph(regions.graph.clone(), state);
+                assert_eq!(fresh.territories, regions.territories);
+                assert_eq!(fresh.frontiers, regions.frontiers);
+            }
+
+            while let Some((checkpoint, region_checkpoint)) = checkpoints.pop() {
+                state.restore(checkpoint);
+                Position::restore(&mut regions, region_checkpoint);
+                assert_eq!(regions.state.player1, state.player1);
+                assert_eq!(regions.state.player2, state.player2);
+            }
+        }
+    }
+
+    fn contains(outer: Mask, inner: Mask) -> bool {
+        outer & inner == inner
+    }
+
+    #[test]
+    fn graph() {
+        for seed in 0..100 {
+            let state = State::generate(seed);
+            let graph = RegionGraph::new(&state.colors);
+            let colored = state
+                .colors
+                .iter()
+                .fold(Mask::empty(), |all, &color| all | color);
+            assert_eq!(colored, !(state.walls | state.player1 | state.player2));
+
+            for cell in 0..256 {
+                match graph.labels[cell] {
+                    Some(label) => {
+                        let region = &graph.regions[usize::from(label)];
+                        assert!(contains(region.cells, Mask::from_index(cell)));
+                        assert!(contains(state.colors[region.color as usize], region.cells));
+                    }
+                    None => assert!(!contains(colored, Mask::from_index(cell))),
+                }
+            }
+
+            let mut cells = Mask::empty();
+            for (index, region) in graph.regions.iter().enumerate() {
+                let index = Mask::from_index(index);
+                assert!((cells & region.cells).is_empty());
+                cells |= region.cells;
+                assert_eq!(graph.containing(region.cells), index);
+                assert!(contains(graph.by_color[region.color as usize], index));
+
+                assert!((region.neighbors & index).is_empty());
+                assert_eq!(graph.neighbors(index), region.neighbors);
+                assert_eq!(graph.touching(region.cells), region.neighbors);
+                for neighbor in region.neighbors.indices() {
+                    let neighbor = &graph.regions[neighbor];
+                    assert!(contains(neighbor.neighbors, index));
+                    assert_ne!(neighbor.color, region.color);
+                }
+            }
+            assert_eq!(cells, colored);
+            assert_eq!(
+                graph.cells(Mask::from_indices(0..graph.regions.len())),
+                colored
+            );
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::bot::{FEATURES, Player, Weighted, features};
+use crate::datagen::{parallel, self_play};
+
+/// A position seen during self-play, labelled with the final margin of its game.
+#[derive(Clone, Copy, Debug)]
+pub struct Sample {
+    pub features: [f64; FEATURES.len()],
+    pub margin: f64,
+}
+
+/// Plays `games` self-play games on seeds `seed..seed + games` and collects every position.
+pub fn collect(games: u64, seed: u64, make: impl Fn() -> Box<dyn Player> + Sync) -> Vec<Sample> {
+    parallel(games, |game| self_play(seed + game, make(), make()))
+        .into_iter()
+        .flatten()
+        .map(|sample| Sample {
+            features: features(&sample.record.state),
+            margin: f64::from(sample.margin),
+        })
+        .collect()
+}
+
+/// Mean squared error between the predicted and the final margins.
+pub fn error(weights: &Weighted, samples: &[Sample]) -> f64 {
+    let total: f64 = samples
+        .iter()
+        .map(|sample| (weights.predict(&sample.features) - sample.margin).powi(2))
+        .sum();
+    total / samples.len().max(1) as f64
+}
+
+/// Fits the weights minimizing `error` by solving the (slightly regularized) normal equations.
+pub fn fit(samples: &[Sample]) -> Weighted {
+    const N: usize = FEATURES.len();
+    const RIDGE: f64 = 1e-6;
+
+    let mut a = [[0.0; N]; N];
+    let mut b = [0.0; N];
+    for sample in samples {
+        for ((row, rhs), x) in a.iter_mut().zip(&mut b).zip(sample.features) {
+            for (cell, y) in row.iter_mut().zip(sample.features) {
+                *cell += x * y;
+            }
+            *rhs += x * sample.margin;
+        }
+    }
+    for (i, row) in a.iter_mut().enumerate() {
+        row[i] += RIDGE * samples.len().max(1) as f64;
+    }
+
+    // Gaussian elimination with partial pivoting.
+    for col in 0..N {
+        let pivot = (col..N)
+            .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+            .unwrap();
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..N {
+            let factor = a[row][col] / a[col][col];
+            let pivot_row = a[col];
+            for (cell, pivot) in a[row].iter_mut().zip(pivot_row).skip(col) {
+                *cell -= factor * pivot;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+
+    let mut weights = [0.0; N];
+    for row in (0..N).rev() {
+        let rest: f64 = (row + 1..N).map(|k| a[row][k] * weights[k]).sum();
+        weights[row] = (b[row] - rest) / a[row][row];
+    }
+    Weighted(weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_pcg::Pcg64;
+
+    use super::*;
+    use crate::bot::Greedy;
+
+    #[test]
+    fn fit_recovers_weights() {
+        let mut rng = Pcg64::seed_from_u64(0);
+        let weights = Weighted([0.5, -1.25, 2.0, 0.125, 3.0]);
+        let samples: Vec<_> = (0..500)
+            .map(|_| {
+                let features = [(); FEATURES.len()].map(|()| rng.random_range(-50.0..50.0));
+                Sample {
+                    features,
+                    margin: weights.predict(&features),
+                }
+            })
+            .collect();
+
+        let fitted = fit(&samples);
+        for (fitted, weight) in fitted.0.into_iter().zip(weights.0) {
+            assert!((fitted - weight).abs() < 1e-4, "{fitted} != {weight}");
+        }
+        assert!(error(&fitted, &samples) < 1e-6);
+        assert!(error(&Weighted::default(), &samples) > 1.0);
+    }
+
+    #[test]
+    fn fit_beats_default() {
+        let samples = collect(8, 0, || Box::new(Greedy));
+        assert!(!samples.is_empty());
+        let fitted = fit(&samples);
+        assert!(error(&fitted, &samples) <= error(&Weighted::default(), &samples));
+        assert!(error(&fitted, &[]).abs() < f64::EPSILON);
+    }
+}
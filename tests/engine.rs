@@ -0,0 +1,69 @@
+//! Plays games against the `engine` binary, which must play exactly like the same contestant
+//! in process.
+
+use flood_it_bot::arena::{find_contestant, play_game};
+use flood_it_bot::bot::Player;
+use flood_it_bot::engine::ExternalPlayer;
+use flood_it_bot::game::{Color, State};
+
+const CONTESTANT: &str = "Greedy";
+
+fn engine() -> ExternalPlayer {
+    ExternalPlayer::spawn(env!("CARGO_BIN_EXE_engine"), &[CONTESTANT]).unwrap()
+}
+
+fn contestant() -> Box<dyn Player> {
+    (find_contestant(CONTESTANT).unwrap().make)()
+}
+
+/// An opponent that plays differently from the contestant: the last valid move.
+struct Last;
+
+impl Player for Last {
+    fn play(&mut self, state: &State) -> Color {
+        state.valid_moves().last().unwrap()
+    }
+}
+
+#[test]
+fn engine_plays_like_contestant() {
+    let mut engine = engine();
+    assert_eq!(engine.name(), CONTESTANT);
+
+    // The same engine plays several games in a row, from both seats.
+    for seed in [3, 4] {
+        let expected = play_game(seed, &mut contestant(), &mut Last);
+        let result = play_game(seed, &mut engine, &mut Last);
+        assert_eq!(result.margin, expected.margin);
+        assert_eq!(result.rounds, expected.rounds);
+        assert!(!result.p1_forfeited && !result.p2_forfeited);
+
+        let expected = play_game(seed, &mut Last, &mut contestant());
+        let result = play_game(seed, &mut Last, &mut engine);
+        assert_eq!(result.margin, expected.margin);
+        assert_eq!(result.rounds, expected.rounds);
+        assert!(!result.p1_forfeited && !result.p2_forfeited);
+    }
+}
+
+#[test]
+fn engine_joins_games_midway() {
+    let mut engine = engine();
+    let mut contestant = contestant();
+
+    // Positions the engine has not seen the moves of are sent as a state.
+    let mut state = State::generate(5);
+    for _ in 0..7 {
+        let color = Last.play(&state);
+        state.play(color);
+    }
+    while !state.game_over() {
+        let color = engine.try_play(&state, None).unwrap();
+        assert_eq!(color, contestant.play(&state));
+        state.play(color);
+        if !state.game_over() {
+            let color = Last.play(&state);
+            state.play(color);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+#![cfg(feature = "serde")]
+
+use std::time::Duration;
+
+use flood_it_bot::arena::{ContestantStats, GameResult, MatchResult};
+use flood_it_bot::bot::Record;
+use flood_it_bot::game::{Checkpoint, Color, State};
+use flood_it_bot::mask::Mask;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// A few positions from every stage of a game.
+fn states() -> Vec<State> {
+    let mut states = Vec::new();
+    for seed in 0..20 {
+        let mut state = State::generate(seed);
+        states.push(state);
+        while !state.game_over() {
+            let moves: Vec<_> = state.valid_moves().collect();
+            state.play(moves[(seed as usize + state.round as usize) % moves.len()]);
+            states.push(state);
+        }
+    }
+    states
+}
+
+/// Round trips `value` through JSON and postcard, and returns both results.
+fn round_trip<T: Serialize + DeserializeOwned>(value: &T) -> [T; 2] {
+    let json = serde_json::to_string(value).unwrap();
+    let bytes = postcard::to_stdvec(value).unwrap();
+    [
+        serde_json::from_str(&json).unwrap(),
+        postcard::from_bytes(&bytes).unwrap(),
+    ]
+}
+
+fn same_state(a: &State, b: &State) -> bool {
+    a.colors == b.colors
+        && a.walls == b.walls
+        && a.player1 == b.player1
+        && a.player2 == b.player2
+        && a.player1_last_move == b.player1_last_move
+        && a.player2_last_move == b.player2_last_move
+        && a.round == b.round
+        && a.seed == b.seed
+}
+
+#[test]
+fn masks() {
+    for state in states() {
+        for mask in state.colors.into_iter().chain([state.player1, state.walls]) {
+            assert_eq!(round_trip(&mask), [mask; 2]);
+            assert_eq!(
+                serde_json::to_string(&mask).unwrap(),
+                format!("\"{mask:x}\"")
+            );
+        }
+    }
+    assert!(serde_json::from_str::<Mask>("\"0123\"").is_err());
+    assert!(serde_json::from_str::<Mask>(&format!("\"{}\"", "g".repeat(64))).is_err());
+}
+
+#[test]
+fn colors() {
+    for color in Color::LIST {
+        assert_eq!(round_trip(&color), [color; 2]);
+        assert_eq!(
+            serde_json::to_string(&color).unwrap(),
+            format!("\"{color}\"")
+        );
+    }
+}
+
+#[test]
+fn states_and_checkpoints() {
+    for state in states() {
+        for copy in round_trip(&state) {
+            assert!(same_state(&copy, &state));
+            assert!(copy.is_valid());
+        }
+
+        let checkpoint = state.checkpoint();
+        for copy in round_trip(&checkpoint) {
+            let Checkpoint {
+                players,
+                player1_last_move,
+                player2_last_move,
+                round,
+            } = copy;
+            assert_eq!(players, checkpoint.players);
+            assert_eq!(player1_last_move, checkpoint.player1_last_move);
+            assert_eq!(player2_last_move, checkpoint.player2_last_move);
+            assert_eq!(round, checkpoint.round);
+        }
+
+        let canonical = state.canonical();
+        assert_eq!(round_trip(&canonical), [canonical; 2]);
+    }
+}
+
+#[test]
+fn records() {
+    for state in states().into_iter().filter(|state| !state.game_over()) {
+        let record = Record {
+            state,
+            color: state.valid_moves().next().unwrap(),
+            score: Some(state.round as i32 - 50),
+        };
+        for copy in round_trip(&record) {
+            assert!(same_state(&copy.state, &record.state));
+            assert_eq!(copy.color, record.color);
+            assert_eq!(copy.score, record.score);
+        }
+    }
+}
+
+#[test]
+fn results() {
+    let game = GameResult {
+        margin: -17,
+        p1_time: Duration::from_micros(1_234_567),
+        p2_time: Duration::from_millis(890),
+        p1_forfeited: false,
+        p2_forfeited: true,
+        rounds: 43,
+    };
+    for copy in round_trip(&game) {
+        assert_eq!(format!("{copy:?}"), format!("{game:?}"));
+    }
+
+    let result = MatchResult::new(1, 2, game, game);
+    for copy in round_trip(&result) {
+        assert_eq!(format!("{copy:?}"), format!("{result:?}"));
+    }
+
+    let mut stats = [ContestantStats::new(); 3];
+    result.update(&mut stats);
+    for copy in round_trip(&stats) {
+        assert_eq!(format!("{copy:?}"), format!("{stats:?}"));
+    }
+}